@@ -7,7 +7,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 fn main() {
     let client_id = "YOUR_CLIENT_ID";
 
-    let mut client = match Client::new(client_id) {
+    let client = match Client::new(client_id) {
         Ok(client) => {
             println!("Connected!");
             client
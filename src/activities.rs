@@ -1,15 +1,38 @@
-use serde::Serialize;
-use serde_repr::Serialize_repr;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The maximum number of buttons an activity can have.
+pub const MAX_BUTTONS: usize = 2;
+
+/// The maximum length of a button label, in characters.
+pub const MAX_BUTTON_LABEL_LEN: usize = 32;
+
+/// The maximum length of the details, the state and the asset texts, in characters.
+pub const MAX_TEXT_LEN: usize = 128;
+
+/// The placeholder shown in place of a secret.
+pub const REDACTED: &str = "<redacted>";
+
+/// The fields of `Secrets` that hold secrets.
+pub(crate) const SECRET_FIELDS: [&str; 3] = ["join", "spectate", "match"];
 
 /// Represents a user's activity on Discord.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Activity {
     /// The user's activity status.
     #[serde(skip_serializing_if = "Option::is_none")]
     details: Option<String>,
+    /// The URL the details link to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details_url: Option<String>,
     /// The user's current party status.
     #[serde(skip_serializing_if = "Option::is_none")]
     state: Option<String>,
+    /// The URL the state links to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_url: Option<String>,
     /// The assets for the activity.
     #[serde(skip_serializing_if = "Option::is_none")]
     assets: Option<Assets>,
@@ -35,7 +58,7 @@ pub struct Activity {
 }
 
 /// Represents the assets for an activity.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Assets {
     /// The ID of the large image asset.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -58,7 +81,7 @@ pub struct Assets {
 }
 
 /// Represents the timestamps for an activity.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Timestamps {
     /// The start time of the activity.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -69,7 +92,7 @@ pub struct Timestamps {
 }
 
 /// Represents the party information for an activity.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Party {
     /// The ID of the party.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -80,7 +103,11 @@ pub struct Party {
 }
 
 /// Represents the secrets for an activity.
-#[derive(Clone, Debug, Serialize)]
+///
+/// The `Debug` output redacts the secrets. With the `zeroize` feature, the secrets are
+/// wiped from memory when dropped.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "zeroize", derive(zeroize::Zeroize, zeroize::ZeroizeOnDrop))]
 pub struct Secrets {
     /// The secret for joining a party.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -97,17 +124,19 @@ pub struct Secrets {
 }
 
 /// Represents a button for an activity.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Button {
     /// The text on the button.
+    #[serde(skip_serializing_if = "Option::is_none")]
     label: Option<String>,
     /// The URL the button opens.
+    #[serde(skip_serializing_if = "Option::is_none")]
     url: Option<String>,
 }
 
 /// Represents the type of an activity.
 #[repr(u8)]
-#[derive(Clone, Debug, Serialize_repr)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
 pub enum ActivityType {
     /// The user is playing a game.
     Playing = 0,
@@ -125,7 +154,7 @@ pub enum ActivityType {
 
 /// Represents the type of status to display.
 #[repr(u8)]
-#[derive(Clone, Debug, Serialize_repr)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
 pub enum StatusDisplayType {
     /// Display the name of the activity.
     Name = 0,
@@ -140,7 +169,9 @@ impl Activity {
     pub fn new() -> Self {
         Activity {
             details: None,
+            details_url: None,
             state: None,
+            state_url: None,
             assets: None,
             timestamps: None,
             party: None,
@@ -151,59 +182,267 @@ impl Activity {
         }
     }
 
+    /// Validates the activity against Discord's constraints.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(buttons) = &self.buttons {
+            if buttons.len() > MAX_BUTTONS {
+                return Err(Error::TooManyButtons {
+                    count: buttons.len(),
+                    max: MAX_BUTTONS,
+                });
+            }
+            for button in buttons {
+                button.validate()?;
+            }
+        }
+
+        if let Some(secrets) = &self.secrets {
+            let has_buttons = self
+                .buttons
+                .as_ref()
+                .is_some_and(|buttons| !buttons.is_empty());
+            if has_buttons && (secrets.join.is_some() || secrets.spectate.is_some()) {
+                return Err(Error::ButtonsWithSecrets);
+            }
+            let has_secret =
+                secrets.join.is_some() || secrets.spectate.is_some() || secrets.r#match.is_some();
+            let has_party_id = self.party.as_ref().is_some_and(|party| party.id.is_some());
+            if has_secret && !has_party_id {
+                return Err(Error::SecretsWithoutParty);
+            }
+        }
+        Ok(())
+    }
+
+    /// Truncates the details, the state and the asset texts to `MAX_TEXT_LEN`.
+    ///
+    /// A text that is cut ends with an ellipsis, which counts towards the limit.
+    /// With the `unicode` feature, texts are only cut between grapheme clusters, so
+    /// emoji and combining sequences are never split.
+    pub fn truncate_text(mut self) -> Self {
+        let assets = self.assets.as_mut();
+        let (large_text, small_text) = match assets {
+            Some(assets) => (assets.large_text.as_mut(), assets.small_text.as_mut()),
+            None => (None, None),
+        };
+        let texts = [
+            self.details.as_mut(),
+            self.state.as_mut(),
+            large_text,
+            small_text,
+        ];
+        for text in texts.into_iter().flatten() {
+            truncate(text, MAX_TEXT_LEN);
+        }
+        self
+    }
+
+    /// Converts the activity into a JSON value.
+    pub fn to_json_value(&self) -> Result<serde_json::Value, Error> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    /// Converts the activity into a JSON string.
+    pub fn to_json_string(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Creates an `Activity` from a JSON value.
+    pub fn from_json_value(value: serde_json::Value) -> Result<Self, Error> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Creates an `Activity` from a JSON string.
+    pub fn from_json_str(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Returns the details of the activity.
+    pub fn details(&self) -> Option<&str> {
+        self.details.as_deref()
+    }
+
+    /// Returns the URL the details link to.
+    pub fn details_url(&self) -> Option<&str> {
+        self.details_url.as_deref()
+    }
+
+    /// Returns the state of the activity.
+    pub fn state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+
+    /// Returns the URL the state links to.
+    pub fn state_url(&self) -> Option<&str> {
+        self.state_url.as_deref()
+    }
+
+    /// Returns the assets of the activity.
+    pub fn assets(&self) -> Option<&Assets> {
+        self.assets.as_ref()
+    }
+
+    /// Returns the timestamps of the activity.
+    pub fn timestamps(&self) -> Option<&Timestamps> {
+        self.timestamps.as_ref()
+    }
+
+    /// Returns the party of the activity.
+    pub fn party(&self) -> Option<&Party> {
+        self.party.as_ref()
+    }
+
+    /// Returns the secrets of the activity.
+    pub fn secrets(&self) -> Option<&Secrets> {
+        self.secrets.as_ref()
+    }
+
+    /// Returns the buttons of the activity.
+    pub fn buttons(&self) -> Option<&[Button]> {
+        self.buttons.as_deref()
+    }
+
+    /// Returns the type of the activity.
+    pub fn activity_type(&self) -> Option<&ActivityType> {
+        self.activity_type.as_ref()
+    }
+
+    /// Returns the type of status to display.
+    pub fn status_display_type(&self) -> Option<&StatusDisplayType> {
+        self.status_display_type.as_ref()
+    }
+
     /// Sets the details of the activity.
     pub fn set_details(mut self, details: String) -> Self {
         self.details = Some(details);
         self
     }
 
+    /// Sets the details of the activity in place.
+    pub fn details_mut(&mut self, details: String) -> &mut Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Sets the URL the details of the activity link to.
+    pub fn set_details_url(mut self, details_url: String) -> Self {
+        self.details_url = Some(details_url);
+        self
+    }
+
+    /// Sets the URL the details of the activity link to in place.
+    pub fn details_url_mut(&mut self, details_url: String) -> &mut Self {
+        self.details_url = Some(details_url);
+        self
+    }
+
     /// Sets the state of the activity.
     pub fn set_state(mut self, state: String) -> Self {
         self.state = Some(state);
         self
     }
 
+    /// Sets the state of the activity in place.
+    pub fn state_mut(&mut self, state: String) -> &mut Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Sets the URL the state of the activity links to.
+    pub fn set_state_url(mut self, state_url: String) -> Self {
+        self.state_url = Some(state_url);
+        self
+    }
+
+    /// Sets the URL the state of the activity links to in place.
+    pub fn state_url_mut(&mut self, state_url: String) -> &mut Self {
+        self.state_url = Some(state_url);
+        self
+    }
+
     /// Sets the assets of the activity.
     pub fn set_assets(mut self, assets: Assets) -> Self {
         self.assets = Some(assets);
         self
     }
 
+    /// Sets the assets of the activity in place.
+    pub fn assets_mut(&mut self, assets: Assets) -> &mut Self {
+        self.assets = Some(assets);
+        self
+    }
+
     /// Sets the timestamps of the activity.
     pub fn set_timestamps(mut self, timestamps: Timestamps) -> Self {
         self.timestamps = Some(timestamps);
         self
     }
 
+    /// Sets the timestamps of the activity in place.
+    pub fn timestamps_mut(&mut self, timestamps: Timestamps) -> &mut Self {
+        self.timestamps = Some(timestamps);
+        self
+    }
+
     /// Sets the party of the activity.
     pub fn set_party(mut self, party: Party) -> Self {
         self.party = Some(party);
         self
     }
 
+    /// Sets the party of the activity in place.
+    pub fn party_mut(&mut self, party: Party) -> &mut Self {
+        self.party = Some(party);
+        self
+    }
+
     /// Sets the secrets of the activity.
     pub fn set_secrets(mut self, secrets: Secrets) -> Self {
         self.secrets = Some(secrets);
         self
     }
 
+    /// Sets the secrets of the activity in place.
+    pub fn secrets_mut(&mut self, secrets: Secrets) -> &mut Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
     /// Sets the buttons of the activity.
     pub fn set_buttons(mut self, buttons: Vec<Button>) -> Self {
         self.buttons = Some(buttons);
         self
     }
 
+    /// Sets the buttons of the activity in place.
+    pub fn buttons_mut(&mut self, buttons: Vec<Button>) -> &mut Self {
+        self.buttons = Some(buttons);
+        self
+    }
+
     /// Sets the type of the activity.
     pub fn set_activity_type(mut self, activity_type: ActivityType) -> Self {
         self.activity_type = Some(activity_type);
         self
     }
 
+    /// Sets the type of the activity in place.
+    pub fn activity_type_mut(&mut self, activity_type: ActivityType) -> &mut Self {
+        self.activity_type = Some(activity_type);
+        self
+    }
+
     /// Sets the status display type of the activity.
     pub fn set_status_display_type(mut self, status_display_type: StatusDisplayType) -> Self {
         self.status_display_type = Some(status_display_type);
         self
     }
+
+    /// Sets the status display type of the activity in place.
+    pub fn status_display_type_mut(&mut self, status_display_type: StatusDisplayType) -> &mut Self {
+        self.status_display_type = Some(status_display_type);
+        self
+    }
 }
 
 impl Assets {
@@ -219,41 +458,107 @@ impl Assets {
         }
     }
 
+    /// Returns the ID of the large image asset.
+    pub fn large_image(&self) -> Option<&str> {
+        self.large_image.as_deref()
+    }
+
+    /// Returns the text shown when hovering over the large image asset.
+    pub fn large_text(&self) -> Option<&str> {
+        self.large_text.as_deref()
+    }
+
+    /// Returns the URL of the large image asset.
+    pub fn large_url(&self) -> Option<&str> {
+        self.large_url.as_deref()
+    }
+
+    /// Returns the ID of the small image asset.
+    pub fn small_image(&self) -> Option<&str> {
+        self.small_image.as_deref()
+    }
+
+    /// Returns the text shown when hovering over the small image asset.
+    pub fn small_text(&self) -> Option<&str> {
+        self.small_text.as_deref()
+    }
+
+    /// Returns the URL of the small image asset.
+    pub fn small_url(&self) -> Option<&str> {
+        self.small_url.as_deref()
+    }
+
     /// Sets the large image of the assets.
     pub fn set_large_image(mut self, large_image: String) -> Self {
         self.large_image = Some(large_image);
         self
     }
 
+    /// Sets the large image of the assets in place.
+    pub fn large_image_mut(&mut self, large_image: String) -> &mut Self {
+        self.large_image = Some(large_image);
+        self
+    }
+
     /// Sets the large text of the assets.
     pub fn set_large_text(mut self, large_text: String) -> Self {
         self.large_text = Some(large_text);
         self
     }
 
+    /// Sets the large text of the assets in place.
+    pub fn large_text_mut(&mut self, large_text: String) -> &mut Self {
+        self.large_text = Some(large_text);
+        self
+    }
+
     /// Sets the large URL of the assets.
     pub fn set_large_url(mut self, large_url: String) -> Self {
         self.large_url = Some(large_url);
         self
     }
 
+    /// Sets the large URL of the assets in place.
+    pub fn large_url_mut(&mut self, large_url: String) -> &mut Self {
+        self.large_url = Some(large_url);
+        self
+    }
+
     /// Sets the small image of the assets.
     pub fn set_small_image(mut self, small_image: String) -> Self {
         self.small_image = Some(small_image);
         self
     }
 
+    /// Sets the small image of the assets in place.
+    pub fn small_image_mut(&mut self, small_image: String) -> &mut Self {
+        self.small_image = Some(small_image);
+        self
+    }
+
     /// Sets the small text of the assets.
     pub fn set_small_text(mut self, small_text: String) -> Self {
         self.small_text = Some(small_text);
         self
     }
 
+    /// Sets the small text of the assets in place.
+    pub fn small_text_mut(&mut self, small_text: String) -> &mut Self {
+        self.small_text = Some(small_text);
+        self
+    }
+
     /// Sets the small URL of the assets.
     pub fn set_small_url(mut self, small_url: String) -> Self {
         self.small_url = Some(small_url);
         self
     }
+
+    /// Sets the small URL of the assets in place.
+    pub fn small_url_mut(&mut self, small_url: String) -> &mut Self {
+        self.small_url = Some(small_url);
+        self
+    }
 }
 
 impl Party {
@@ -265,17 +570,39 @@ impl Party {
         }
     }
 
+    /// Returns the ID of the party.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Returns the current and maximum size of the party.
+    pub fn size(&self) -> Option<[u32; 2]> {
+        self.size
+    }
+
     /// Sets the ID of the party.
     pub fn set_id(mut self, id: String) -> Self {
         self.id = Some(id);
         self
     }
 
+    /// Sets the ID of the party in place.
+    pub fn id_mut(&mut self, id: String) -> &mut Self {
+        self.id = Some(id);
+        self
+    }
+
     /// Sets the size of the party.
     pub fn set_size(mut self, present: u32, size: u32) -> Self {
         self.size = Some([present, size]);
         self
     }
+
+    /// Sets the size of the party in place.
+    pub fn size_mut(&mut self, present: u32, size: u32) -> &mut Self {
+        self.size = Some([present, size]);
+        self
+    }
 }
 
 impl Timestamps {
@@ -287,17 +614,68 @@ impl Timestamps {
         }
     }
 
+    /// Creates the timestamps that render a progress bar at `position` of `total`.
+    ///
+    /// The start is placed `position` before now and the end `total` after the start,
+    /// both in Unix milliseconds. The position is clamped to `total`, so a seek past
+    /// the end shows a full bar.
+    pub fn for_progress(position: Duration, total: Duration) -> Self {
+        let start = Self::elapsed_start(position.min(total));
+        Timestamps {
+            start: Some(start.as_millis() as u64),
+            end: Some((start + total).as_millis() as u64),
+        }
+    }
+
+    /// Creates the timestamps that render an elapsed counter already at `elapsed`.
+    pub fn for_elapsed(elapsed: Duration) -> Self {
+        Timestamps {
+            start: Some(Self::elapsed_start(elapsed).as_millis() as u64),
+            end: None,
+        }
+    }
+
+    /// Returns the time `elapsed` before now, as a `Duration` since the Unix epoch.
+    fn elapsed_start(elapsed: Duration) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_sub(elapsed)
+    }
+
+    /// Returns the start time of the timestamps.
+    pub fn start(&self) -> Option<u64> {
+        self.start
+    }
+
+    /// Returns the end time of the timestamps.
+    pub fn end(&self) -> Option<u64> {
+        self.end
+    }
+
     /// Sets the start time of the timestamps.
     pub fn set_start(mut self, start: u64) -> Self {
         self.start = Some(start);
         self
     }
 
+    /// Sets the start time of the timestamps in place.
+    pub fn start_mut(&mut self, start: u64) -> &mut Self {
+        self.start = Some(start);
+        self
+    }
+
     /// Sets the end time of the timestamps.
     pub fn set_end(mut self, end: u64) -> Self {
         self.end = Some(end);
         self
     }
+
+    /// Sets the end time of the timestamps in place.
+    pub fn end_mut(&mut self, end: u64) -> &mut Self {
+        self.end = Some(end);
+        self
+    }
 }
 
 impl Secrets {
@@ -311,29 +689,108 @@ impl Secrets {
         }
     }
 
+    /// Returns the join secret of the secrets.
+    pub fn join(&self) -> Option<&str> {
+        self.join.as_deref()
+    }
+
+    /// Returns the spectate secret of the secrets.
+    pub fn spectate(&self) -> Option<&str> {
+        self.spectate.as_deref()
+    }
+
+    /// Returns the instance of the secrets.
+    pub fn instance(&self) -> Option<bool> {
+        self.instance
+    }
+
+    /// Returns the match secret of the secrets.
+    pub fn r#match(&self) -> Option<&str> {
+        self.r#match.as_deref()
+    }
+
     /// Sets the join secret of the secrets.
     pub fn set_join(mut self, join: String) -> Self {
         self.join = Some(join);
         self
     }
 
+    /// Sets the join secret of the secrets in place.
+    pub fn join_mut(&mut self, join: String) -> &mut Self {
+        self.join = Some(join);
+        self
+    }
+
     /// Sets the spectate secret of the secrets.
     pub fn set_spectate(mut self, spectate: String) -> Self {
         self.spectate = Some(spectate);
         self
     }
 
+    /// Sets the spectate secret of the secrets in place.
+    pub fn spectate_mut(&mut self, spectate: String) -> &mut Self {
+        self.spectate = Some(spectate);
+        self
+    }
+
     /// Sets the instance of the secrets.
     pub fn set_instance(mut self, instance: bool) -> Self {
         self.instance = Some(instance);
         self
     }
 
+    /// Sets the instance of the secrets in place.
+    pub fn instance_mut(&mut self, instance: bool) -> &mut Self {
+        self.instance = Some(instance);
+        self
+    }
+
     /// Sets the match secret of the secrets.
     pub fn set_match(mut self, r#match: String) -> Self {
         self.r#match = Some(r#match);
         self
     }
+
+    /// Sets the match secret of the secrets in place.
+    pub fn match_mut(&mut self, r#match: String) -> &mut Self {
+        self.r#match = Some(r#match);
+        self
+    }
+}
+
+impl std::fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Secrets")
+            .field("join", &self.join.as_ref().map(|_| Redacted))
+            .field("spectate", &self.spectate.as_ref().map(|_| Redacted))
+            .field("instance", &self.instance)
+            .field("match", &self.r#match.as_ref().map(|_| Redacted))
+            .finish()
+    }
+}
+
+/// Stands in for a secret in `Debug` output.
+pub(crate) struct Redacted;
+
+impl std::fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Replaces the secrets in the JSON representation of an activity with `REDACTED`.
+pub fn redact_secrets(activity: &mut serde_json::Value) {
+    let Some(secrets) = activity
+        .get_mut("secrets")
+        .and_then(serde_json::Value::as_object_mut)
+    else {
+        return;
+    };
+    for field in SECRET_FIELDS {
+        if let Some(secret) = secrets.get_mut(field).filter(|secret| !secret.is_null()) {
+            *secret = serde_json::Value::String(REDACTED.to_string());
+        }
+    }
 }
 
 impl Button {
@@ -345,15 +802,281 @@ impl Button {
         }
     }
 
+    /// Returns the label of the button.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Returns the URL of the button.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Validates the button against Discord's constraints.
+    pub fn validate(&self) -> Result<(), Error> {
+        let label = self.label.as_deref().ok_or(Error::ButtonLabelMissing)?;
+        let len = label.chars().count();
+        if len == 0 {
+            return Err(Error::ButtonLabelMissing);
+        }
+        if len > MAX_BUTTON_LABEL_LEN {
+            return Err(Error::ButtonLabelTooLong {
+                len,
+                max: MAX_BUTTON_LABEL_LEN,
+            });
+        }
+
+        let url = self.url.as_deref().ok_or(Error::ButtonUrlMissing)?;
+        let scheme = url.split_once("://").map(|(scheme, _)| scheme);
+        match scheme.map(str::to_ascii_lowercase).as_deref() {
+            Some("https") | Some("discord") => Ok(()),
+            _ => Err(Error::InvalidButtonUrl(url.to_string())),
+        }
+    }
+
     /// Sets the label of the button.
     pub fn set_label(mut self, label: String) -> Self {
         self.label = Some(label);
         self
     }
 
+    /// Sets the label of the button in place.
+    pub fn label_mut(&mut self, label: String) -> &mut Self {
+        self.label = Some(label);
+        self
+    }
+
     /// Sets the URL of the button.
     pub fn set_url(mut self, url: String) -> Self {
         self.url = Some(url);
         self
     }
+
+    /// Sets the URL of the button in place.
+    pub fn url_mut(&mut self, url: String) -> &mut Self {
+        self.url = Some(url);
+        self
+    }
+}
+
+/// Cuts `text` to at most `max` characters, ending it with an ellipsis if it was cut.
+fn truncate(text: &mut String, max: usize) {
+    if text.chars().count() <= max {
+        return;
+    }
+    text.truncate(cut_index(text, max.saturating_sub(1)));
+    if max > 0 {
+        text.push('\u{2026}');
+    }
+}
+
+/// Returns the byte index just past the first `chars` characters of `text`.
+#[cfg(not(feature = "unicode"))]
+fn cut_index(text: &str, chars: usize) -> usize {
+    text.char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(index, _)| index)
+}
+
+/// Returns the byte index just past the whole grapheme clusters of `text` that fit in
+/// `chars` characters.
+#[cfg(feature = "unicode")]
+fn cut_index(text: &str, chars: usize) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let mut count = 0;
+    let mut end = 0;
+    for (index, grapheme) in text.grapheme_indices(true) {
+        count += grapheme.chars().count();
+        if count > chars {
+            break;
+        }
+        end = index + grapheme.len();
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns `text` truncated to `max` characters.
+    fn truncated(text: &str, max: usize) -> String {
+        let mut text = text.to_string();
+        truncate(&mut text, max);
+        text
+    }
+
+    #[test]
+    fn truncate_keeps_short_text() {
+        let text = "a".repeat(MAX_TEXT_LEN);
+        assert_eq!(truncated(&text, MAX_TEXT_LEN), text);
+        assert_eq!(truncated("", MAX_TEXT_LEN), "");
+    }
+
+    #[test]
+    fn truncate_ends_cut_text_with_an_ellipsis() {
+        let text = truncated(&"a".repeat(MAX_TEXT_LEN + 1), MAX_TEXT_LEN);
+        assert_eq!(text.chars().count(), MAX_TEXT_LEN);
+        assert!(text.ends_with("a\u{2026}"));
+    }
+
+    #[test]
+    fn truncate_counts_characters_not_bytes() {
+        let text = "é".repeat(MAX_TEXT_LEN);
+        assert_eq!(truncated(&text, MAX_TEXT_LEN), text);
+
+        let text = truncated(&"é".repeat(MAX_TEXT_LEN + 1), MAX_TEXT_LEN);
+        assert_eq!(text, format!("{}\u{2026}", "é".repeat(MAX_TEXT_LEN - 1)));
+    }
+
+    #[test]
+    fn truncate_handles_grapheme_clusters() {
+        // A thumbs up with a skin tone modifier is two characters and one cluster.
+        let text = format!("{}\u{1f44d}\u{1f3fd}b", "a".repeat(MAX_TEXT_LEN - 2));
+        let text = truncated(&text, MAX_TEXT_LEN);
+        if cfg!(feature = "unicode") {
+            assert_eq!(text, format!("{}\u{2026}", "a".repeat(MAX_TEXT_LEN - 2)));
+        } else {
+            assert_eq!(
+                text,
+                format!("{}\u{1f44d}\u{2026}", "a".repeat(MAX_TEXT_LEN - 2))
+            );
+        }
+    }
+
+    #[test]
+    fn truncate_text_covers_details_state_and_asset_texts() {
+        let long = "a".repeat(MAX_TEXT_LEN + 10);
+        let activity = Activity::new()
+            .set_details(long.clone())
+            .set_state(long.clone())
+            .set_assets(
+                Assets::new()
+                    .set_large_text(long.clone())
+                    .set_small_text(long),
+            )
+            .truncate_text();
+
+        let assets = activity.assets().unwrap();
+        for text in [
+            activity.details(),
+            activity.state(),
+            assets.large_text(),
+            assets.small_text(),
+        ] {
+            assert_eq!(text.unwrap().chars().count(), MAX_TEXT_LEN);
+        }
+    }
+
+    #[test]
+    fn validate_rejects_buttons_with_secrets() {
+        let activity = Activity::new()
+            .set_party(Party::new().set_id("party".to_string()))
+            .set_secrets(Secrets::new().set_join("secret".to_string()))
+            .set_buttons(vec![
+                Button::new()
+                    .set_label("Website".to_string())
+                    .set_url("https://example.com".to_string()),
+            ]);
+        assert!(matches!(
+            activity.validate(),
+            Err(Error::ButtonsWithSecrets)
+        ));
+    }
+
+    #[test]
+    fn validate_requires_a_party_for_secrets() {
+        let activity = Activity::new().set_secrets(Secrets::new().set_join("secret".to_string()));
+        assert!(matches!(
+            activity.validate(),
+            Err(Error::SecretsWithoutParty)
+        ));
+
+        let activity = activity.set_party(Party::new().set_id("party".to_string()));
+        assert!(activity.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_checks_buttons() {
+        let button = Button::new()
+            .set_label("Website".to_string())
+            .set_url("https://example.com".to_string());
+        let activity = Activity::new().set_buttons(vec![button.clone(); MAX_BUTTONS + 1]);
+        assert!(matches!(
+            activity.validate(),
+            Err(Error::TooManyButtons {
+                count: 3,
+                max: MAX_BUTTONS
+            })
+        ));
+
+        let button = button.set_url("http://example.com".to_string());
+        let activity = Activity::new().set_buttons(vec![button]);
+        assert!(matches!(
+            activity.validate(),
+            Err(Error::InvalidButtonUrl(url)) if url == "http://example.com"
+        ));
+    }
+
+    #[test]
+    fn redact_secrets_replaces_every_secret() {
+        let activity = Activity::new()
+            .set_party(Party::new().set_id("party".to_string()))
+            .set_secrets(
+                Secrets::new()
+                    .set_join("join".to_string())
+                    .set_match("match".to_string()),
+            );
+        let mut value = activity.to_json_value().unwrap();
+        redact_secrets(&mut value);
+        assert_eq!(value["secrets"]["join"], REDACTED);
+        assert_eq!(value["secrets"]["match"], REDACTED);
+        assert!(value["secrets"].get("spectate").is_none());
+        assert_eq!(value["party"]["id"], "party");
+    }
+
+    /// Returns the current time in Unix milliseconds.
+    fn now_millis() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64
+    }
+
+    #[test]
+    fn for_progress_places_the_start_position_before_now() {
+        let before = now_millis();
+        let timestamps =
+            Timestamps::for_progress(Duration::from_secs(30), Duration::from_secs(180));
+        let after = now_millis();
+
+        let start = timestamps.start().unwrap();
+        assert!((before - 30_000..=after - 30_000).contains(&start));
+        assert_eq!(timestamps.end(), Some(start + 180_000));
+    }
+
+    #[test]
+    fn for_progress_clamps_a_position_past_the_total() {
+        let before = now_millis();
+        let timestamps =
+            Timestamps::for_progress(Duration::from_secs(300), Duration::from_secs(180));
+        let after = now_millis();
+
+        let start = timestamps.start().unwrap();
+        assert!((before - 180_000..=after - 180_000).contains(&start));
+        let end = timestamps.end().unwrap();
+        assert!((before..=after).contains(&end));
+    }
+
+    #[test]
+    fn for_elapsed_places_the_start_elapsed_before_now() {
+        let before = now_millis();
+        let timestamps = Timestamps::for_elapsed(Duration::from_secs(45));
+        let after = now_millis();
+
+        let start = timestamps.start().unwrap();
+        assert!((before - 45_000..=after - 45_000).contains(&start));
+        assert_eq!(timestamps.end(), None);
+    }
 }
@@ -0,0 +1,101 @@
+#[cfg(not(unix))]
+use discord_presence_rs::daemon::DEFAULT_DAEMON_PORT;
+use discord_presence_rs::daemon::Daemon;
+use discord_presence_rs::discord_connection::Client;
+
+/// The environment variable holding the token clients authenticate with.
+#[cfg(not(unix))]
+const TOKEN_ENV_VAR: &str = "DISCORD_PRESENCE_DAEMON_TOKEN";
+
+/// Writes warnings and errors of the daemon to standard error.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn main() {
+    if log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+
+    let mut args = std::env::args().skip(1);
+    let client_id = match args
+        .next()
+        .or_else(|| std::env::var("DISCORD_CLIENT_ID").ok())
+    {
+        Some(client_id) => client_id,
+        None => {
+            #[cfg(unix)]
+            eprintln!("Usage: discord-presence-daemon <CLIENT_ID> [SOCKET_PATH]");
+            #[cfg(not(unix))]
+            eprintln!("Usage: discord-presence-daemon <CLIENT_ID> [PORT]");
+            std::process::exit(2);
+        }
+    };
+
+    #[cfg(unix)]
+    let address = args
+        .next()
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(discord_presence_rs::daemon::default_socket_path);
+    #[cfg(not(unix))]
+    let address = match args.next().map(|port| port.parse::<u16>()) {
+        Some(Ok(port)) => port,
+        Some(Err(e)) => {
+            eprintln!("Invalid port: {}", e);
+            std::process::exit(2);
+        }
+        None => DEFAULT_DAEMON_PORT,
+    };
+    #[cfg(not(unix))]
+    let token = match std::env::var(TOKEN_ENV_VAR) {
+        Ok(token) if !token.is_empty() => token,
+        _ => {
+            eprintln!(
+                "Set {} to the token clients authenticate with",
+                TOKEN_ENV_VAR
+            );
+            std::process::exit(2);
+        }
+    };
+
+    let client = match Client::new(client_id) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    #[cfg(unix)]
+    let daemon = Daemon::bind(client, address);
+    #[cfg(not(unix))]
+    let daemon = Daemon::bind(client, address, token);
+    let daemon = match daemon {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(unix)]
+    println!("Listening on {}", daemon.socket_path().display());
+    #[cfg(not(unix))]
+    println!("Listening on 127.0.0.1:{}", address);
+
+    if let Err(e) = daemon.run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
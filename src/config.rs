@@ -0,0 +1,148 @@
+use crate::discord_connection::{
+    Client, ClientBuilder, ClientId, DEFAULT_HANDSHAKE_TIMEOUT, DEFAULT_HANDSHAKE_VERSION,
+    DEFAULT_HISTORY_LEN,
+};
+use crate::error::Error;
+use crate::frame::{DEFAULT_MAX_FRAME_SIZE, Utf8Policy};
+use crate::platform::Discovery;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Represents the settings of a `Client`, for embedding in an application's config file.
+///
+/// Every field except `client_id` is optional and falls back to the builder default.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientConfig {
+    /// The client ID of the application.
+    pub client_id: ClientId,
+    /// The maximum payload size accepted in a single frame from Discord.
+    #[serde(default = "default_max_frame_size")]
+    pub max_frame_size: u32,
+    /// How invalid UTF-8 in frames received from Discord is handled.
+    #[serde(default)]
+    pub utf8_policy: Utf8Policy,
+    /// Where to look for the Discord IPC socket, and how to open the pipes on Windows.
+    #[serde(default)]
+    pub discovery: Discovery,
+    /// Whether activities without a start time are stamped with the session start.
+    #[serde(default)]
+    pub auto_elapsed: bool,
+    /// The version of the IPC protocol requested in the handshake.
+    #[serde(default = "default_handshake_version")]
+    pub handshake_version: u32,
+    /// Extra fields sent in the handshake payload.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub handshake_fields: serde_json::Map<String, serde_json::Value>,
+    /// How long to wait for Discord to answer the handshake, in milliseconds.
+    #[serde(default = "default_handshake_timeout_ms")]
+    pub handshake_timeout_ms: u64,
+    /// Whether stale sockets are removed before connecting.
+    #[serde(default)]
+    pub remove_stale_sockets: bool,
+    /// Whether the client connects to Discord, or succeeds without doing anything.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Whether commands are validated and serialized but never sent.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Whether over-long texts are truncated before activities are sent.
+    #[serde(default)]
+    pub truncate_text: bool,
+    /// How many applied activities are kept in the history of the client.
+    #[serde(default = "default_history_len")]
+    pub history_len: usize,
+    /// Whether raw activities are checked against the SET_ACTIVITY schema.
+    #[serde(default)]
+    pub validate_json: bool,
+    /// The socket of a bridge to the Windows Discord client, used under WSL.
+    ///
+    /// Ignored on platforms other than Linux.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wsl_bridge_path: Option<PathBuf>,
+}
+
+/// Returns the default maximum frame size.
+fn default_max_frame_size() -> u32 {
+    DEFAULT_MAX_FRAME_SIZE
+}
+
+/// Returns the default handshake version.
+fn default_handshake_version() -> u32 {
+    DEFAULT_HANDSHAKE_VERSION
+}
+
+/// Returns the default handshake timeout, in milliseconds.
+fn default_handshake_timeout_ms() -> u64 {
+    DEFAULT_HANDSHAKE_TIMEOUT.as_millis() as u64
+}
+
+/// Returns the default history length.
+fn default_history_len() -> usize {
+    DEFAULT_HISTORY_LEN
+}
+
+/// Returns whether clients are enabled by default.
+fn default_enabled() -> bool {
+    true
+}
+
+impl ClientConfig {
+    /// Creates a new `ClientConfig` with default settings.
+    pub fn new(client_id: impl Into<ClientId>) -> Self {
+        ClientConfig {
+            client_id: client_id.into(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            utf8_policy: Utf8Policy::default(),
+            discovery: Discovery::new(),
+            auto_elapsed: false,
+            handshake_version: DEFAULT_HANDSHAKE_VERSION,
+            handshake_fields: serde_json::Map::new(),
+            handshake_timeout_ms: default_handshake_timeout_ms(),
+            remove_stale_sockets: false,
+            enabled: true,
+            dry_run: false,
+            truncate_text: false,
+            history_len: DEFAULT_HISTORY_LEN,
+            validate_json: false,
+            wsl_bridge_path: None,
+        }
+    }
+
+    /// Creates a `ClientBuilder` with these settings.
+    pub fn to_builder(&self) -> ClientBuilder {
+        let mut builder = ClientBuilder::new(self.client_id.clone())
+            .set_max_frame_size(self.max_frame_size)
+            .set_utf8_policy(self.utf8_policy)
+            .set_discovery(self.discovery.clone())
+            .set_auto_elapsed(self.auto_elapsed)
+            .set_handshake_version(self.handshake_version)
+            .set_handshake_timeout(Duration::from_millis(self.handshake_timeout_ms))
+            .set_remove_stale_sockets(self.remove_stale_sockets)
+            .set_enabled(self.enabled)
+            .set_dry_run(self.dry_run)
+            .set_truncate_text(self.truncate_text)
+            .set_history_len(self.history_len)
+            .set_validate_json(self.validate_json);
+        for (key, value) in &self.handshake_fields {
+            builder = builder.add_handshake_field(key.clone(), value.clone());
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(wsl_bridge_path) = &self.wsl_bridge_path {
+            builder = builder.set_wsl_bridge_path(wsl_bridge_path.clone());
+        }
+        builder
+    }
+
+    /// Connects a `Client` with these settings.
+    pub fn connect(&self) -> Result<Client, Error> {
+        self.to_builder().build()
+    }
+}
+
+impl From<ClientConfig> for ClientBuilder {
+    fn from(config: ClientConfig) -> Self {
+        config.to_builder()
+    }
+}
@@ -0,0 +1,549 @@
+use crate::activities::Activity;
+use crate::discord_connection::Client;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+#[cfg(not(unix))]
+use std::net::{Ipv4Addr, SocketAddr, TcpListener as Listener, TcpStream as Stream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Stream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The default port the daemon listens on.
+#[cfg(not(unix))]
+pub const DEFAULT_DAEMON_PORT: u16 = 6480;
+
+/// The file name of the daemon socket.
+#[cfg(unix)]
+const SOCKET_NAME: &str = "discord-presence-daemon.sock";
+
+/// Returns the default path of the daemon socket.
+///
+/// This is `$XDG_RUNTIME_DIR/discord-presence-daemon.sock`, or a socket in a
+/// directory of the current user under the temporary directory if `$XDG_RUNTIME_DIR`
+/// is not set.
+#[cfg(unix)]
+pub fn default_socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) => PathBuf::from(runtime_dir).join(SOCKET_NAME),
+        None => {
+            // SAFETY: `getuid` has no preconditions and cannot fail.
+            let uid = unsafe { libc::getuid() };
+            std::env::temp_dir()
+                .join(format!("discord-presence-daemon-{}", uid))
+                .join(SOCKET_NAME)
+        }
+    }
+}
+
+/// Represents a request sent to the daemon, one JSON object per line.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    /// Sets the activity of a source.
+    Set {
+        /// The name identifying the sending process.
+        source: String,
+        /// The priority of the activity; higher values win.
+        #[serde(default)]
+        priority: i32,
+        /// The activity to display.
+        activity: Box<Activity>,
+    },
+    /// Clears the activity of a source.
+    Clear {
+        /// The name identifying the sending process.
+        source: String,
+    },
+    /// Authenticates the connection. It must be the first request of a connection.
+    #[cfg(not(unix))]
+    Auth {
+        /// The token the daemon was bound with.
+        token: String,
+    },
+}
+
+/// Represents the daemon's response to a request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DaemonResponse {
+    /// Whether the request succeeded.
+    pub ok: bool,
+    /// The error message, if the request failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// An activity submitted by a source.
+struct Entry {
+    connection: u64,
+    priority: i32,
+    sequence: u64,
+    activity: Activity,
+}
+
+/// The state shared between connections.
+struct State {
+    client: Client,
+    entries: HashMap<String, Entry>,
+    sequence: u64,
+    connections: u64,
+    displayed: Option<Activity>,
+}
+
+/// A daemon that owns the Discord connection and multiplexes updates from local processes.
+///
+/// Each source keeps its activity until it clears it or the connection that set it
+/// closes, so a crashed process does not leave its activity behind. The displayed
+/// activity is the one with the highest priority, with ties going to the most recent
+/// update.
+///
+/// On Unix, the daemon listens on a Unix socket that only the current user can
+/// connect to. Elsewhere, it listens on a TCP port on `127.0.0.1`, and every
+/// connection must start with a `DaemonRequest::Auth` carrying the daemon's token.
+pub struct Daemon {
+    listener: Listener,
+    #[cfg(unix)]
+    path: PathBuf,
+    #[cfg(not(unix))]
+    token: String,
+    state: Mutex<State>,
+}
+
+impl Daemon {
+    /// Binds the daemon to a Unix socket at `path`, such as `default_socket_path()`.
+    ///
+    /// A missing parent directory is created so that only the current user can access
+    /// it. An existing parent directory must be owned by the current user and have mode
+    /// `0700`, otherwise a `PermissionDenied` error is returned. The socket itself is
+    /// only accessible to the current user. A stale socket left behind by a daemon that
+    /// crashed is replaced. The socket is removed when the daemon is dropped.
+    #[cfg(unix)]
+    pub fn bind(client: Client, path: impl Into<PathBuf>) -> Result<Self, Error> {
+        use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+        let path = path.into();
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if !parent.exists() {
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(parent)?;
+        }
+        let metadata = std::fs::metadata(parent)?;
+        // SAFETY: `getuid` has no preconditions and cannot fail.
+        let uid = unsafe { libc::getuid() };
+        if metadata.uid() != uid || metadata.mode() & 0o777 != 0o700 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} must be owned by the current user and have mode 0700",
+                    parent.display()
+                ),
+            )
+            .into());
+        }
+        if path.exists()
+            && Stream::connect(&path)
+                .is_err_and(|e| e.kind() == std::io::ErrorKind::ConnectionRefused)
+        {
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = Listener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        Ok(Self::with_listener(client, listener, path))
+    }
+
+    /// Binds the daemon to `127.0.0.1` on the given port.
+    ///
+    /// Connections must authenticate with `token`, which must not be empty.
+    #[cfg(not(unix))]
+    pub fn bind(client: Client, port: u16, token: String) -> Result<Self, Error> {
+        if token.is_empty() {
+            return Err(
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "empty token").into(),
+            );
+        }
+        let listener = Listener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))?;
+        Ok(Self::with_listener(client, listener, token))
+    }
+
+    /// Creates a daemon accepting connections on `listener`.
+    fn with_listener(
+        client: Client,
+        listener: Listener,
+        #[cfg(unix)] path: PathBuf,
+        #[cfg(not(unix))] token: String,
+    ) -> Self {
+        Daemon {
+            listener,
+            #[cfg(unix)]
+            path,
+            #[cfg(not(unix))]
+            token,
+            state: Mutex::new(State {
+                client,
+                entries: HashMap::new(),
+                sequence: 0,
+                connections: 0,
+                displayed: None,
+            }),
+        }
+    }
+
+    /// Returns the path of the socket the daemon is listening on.
+    #[cfg(unix)]
+    pub fn socket_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the address the daemon is listening on.
+    #[cfg(not(unix))]
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accepts connections and handles their requests, each on its own thread.
+    ///
+    /// A connection that fails to be accepted, for example because the process ran out
+    /// of file descriptors, is logged and skipped.
+    pub fn run(&self) -> Result<(), Error> {
+        std::thread::scope(|scope| {
+            for stream in self.listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("Failed to accept a daemon connection: {}", e);
+                        continue;
+                    }
+                };
+                let connection = {
+                    let mut state = self.lock();
+                    state.connections += 1;
+                    state.connections
+                };
+                scope.spawn(move || {
+                    self.handle_connection(connection, stream);
+                    self.disconnect(connection);
+                });
+            }
+            Ok(())
+        })
+    }
+
+    /// Handles every request sent over a connection.
+    fn handle_connection(&self, connection: u64, stream: Stream) {
+        let Ok(mut writer) = stream.try_clone() else {
+            return;
+        };
+        // Only the authentication on TCP reads a line ahead of the loop.
+        #[cfg_attr(unix, allow(unused_mut))]
+        let mut lines = BufReader::new(stream)
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.trim().is_empty());
+
+        #[cfg(not(unix))]
+        {
+            let result = match lines.next() {
+                Some(line) => serde_json::from_str(&line)
+                    .map_err(Error::from)
+                    .and_then(|request| self.authenticate(request)),
+                None => return,
+            };
+            let authenticated = result.is_ok();
+            if !respond(&mut writer, result) || !authenticated {
+                return;
+            }
+        }
+
+        for line in lines {
+            let result = serde_json::from_str(&line)
+                .map_err(Error::from)
+                .and_then(|request| self.handle(connection, request));
+            if !respond(&mut writer, result) {
+                return;
+            }
+        }
+    }
+
+    /// Checks that the first request of a connection carries the daemon's token.
+    #[cfg(not(unix))]
+    fn authenticate(&self, request: DaemonRequest) -> Result<(), Error> {
+        match request {
+            DaemonRequest::Auth { token }
+                if constant_time_eq(token.as_bytes(), self.token.as_bytes()) =>
+            {
+                Ok(())
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "the connection must start with a valid auth request",
+            )
+            .into()),
+        }
+    }
+
+    /// Applies a request and updates the displayed activity.
+    fn handle(&self, connection: u64, request: DaemonRequest) -> Result<(), Error> {
+        let mut state = self.lock();
+        match request {
+            DaemonRequest::Set {
+                source,
+                priority,
+                activity,
+            } => {
+                activity.validate()?;
+                state.sequence += 1;
+                let sequence = state.sequence;
+                state.entries.insert(
+                    source,
+                    Entry {
+                        connection,
+                        priority,
+                        sequence,
+                        activity: *activity,
+                    },
+                );
+            }
+            DaemonRequest::Clear { source } => {
+                state.entries.remove(&source);
+            }
+            // The connection is already authenticated.
+            #[cfg(not(unix))]
+            DaemonRequest::Auth { .. } => return Ok(()),
+        }
+        state.update()
+    }
+
+    /// Removes the activities set over a closed connection.
+    fn disconnect(&self, connection: u64) {
+        let mut state = self.lock();
+        state
+            .entries
+            .retain(|_, entry| entry.connection != connection);
+        if let Err(e) = state.update() {
+            log::warn!("Failed to update the presence: {}", e);
+        }
+    }
+
+    /// Locks the shared state, recovering it if a connection thread panicked.
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Writes the response to a request, returning whether the connection is still usable.
+fn respond(writer: &mut Stream, result: Result<(), Error>) -> bool {
+    let response = match result {
+        Ok(()) => DaemonResponse {
+            ok: true,
+            error: None,
+        },
+        Err(e) => DaemonResponse {
+            ok: false,
+            error: Some(e.to_string()),
+        },
+    };
+    let Ok(mut response) = serde_json::to_string(&response) else {
+        return false;
+    };
+    response.push('\n');
+    writer.write_all(response.as_bytes()).is_ok()
+}
+
+/// Compares two byte strings in time that depends only on their lengths.
+#[cfg(not(unix))]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(unix)]
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl State {
+    /// Displays the winning activity if it changed.
+    fn update(&mut self) -> Result<(), Error> {
+        let winner = self
+            .entries
+            .values()
+            .max_by_key(|entry| (entry.priority, entry.sequence))
+            .map(|entry| entry.activity.clone());
+        if winner == self.displayed {
+            return Ok(());
+        }
+
+        match &winner {
+            Some(activity) => self.client.set_activity(activity.clone())?,
+            None => self.client.clear_activity()?,
+        }
+        self.displayed = winner;
+        Ok(())
+    }
+}
+
+/// A connection to a running daemon.
+pub struct DaemonClient {
+    reader: BufReader<Stream>,
+    writer: Stream,
+}
+
+impl DaemonClient {
+    /// Connects to a daemon listening on the Unix socket at `path`.
+    #[cfg(unix)]
+    pub fn connect(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::with_stream(Stream::connect(path)?)
+    }
+
+    /// Connects to a daemon listening on `127.0.0.1` and the given port, and
+    /// authenticates with `token`.
+    ///
+    /// Returns a `PermissionDenied` I/O error if the daemon rejects the token.
+    #[cfg(not(unix))]
+    pub fn connect(port: u16, token: &str) -> Result<Self, Error> {
+        let stream = Stream::connect(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))?;
+        let mut client = Self::with_stream(stream)?;
+        let response = client.send(&DaemonRequest::Auth {
+            token: token.to_string(),
+        })?;
+        if !response.ok {
+            let message = response.error.unwrap_or_default();
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, message).into());
+        }
+        Ok(client)
+    }
+
+    /// Creates a client sending requests over `writer`.
+    fn with_stream(writer: Stream) -> Result<Self, Error> {
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(DaemonClient { reader, writer })
+    }
+
+    /// Sets the activity for `source` with the given priority.
+    pub fn set_activity(
+        &mut self,
+        source: &str,
+        priority: i32,
+        activity: Activity,
+    ) -> Result<DaemonResponse, Error> {
+        self.send(&DaemonRequest::Set {
+            source: source.to_string(),
+            priority,
+            activity: Box::new(activity),
+        })
+    }
+
+    /// Clears the activity for `source`.
+    pub fn clear_activity(&mut self, source: &str) -> Result<DaemonResponse, Error> {
+        self.send(&DaemonRequest::Clear {
+            source: source.to_string(),
+        })
+    }
+
+    /// Sends a request and waits for the response.
+    fn send(&mut self, request: &DaemonRequest) -> Result<DaemonResponse, Error> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+
+        let mut response = String::new();
+        self.reader.read_line(&mut response)?;
+        Ok(serde_json::from_str(&response)?)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Returns a dry-run client, which needs no running Discord.
+    fn client() -> Client {
+        Client::builder(1234567890123456789u64)
+            .set_dry_run(true)
+            .build()
+            .unwrap()
+    }
+
+    /// Returns a directory path unique to this process and `test`, removing any
+    /// leftover from an earlier run.
+    fn dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "discord-presence-daemon-test-{}-{}",
+            std::process::id(),
+            test
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn bind_creates_a_private_directory() {
+        let dir = dir("creates");
+        let daemon = Daemon::bind(client(), dir.join("daemon.sock")).unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(daemon.socket_path()), 0o600);
+
+        drop(daemon);
+        assert!(!dir.join("daemon.sock").exists());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn bind_refuses_a_shared_directory() {
+        let dir = dir("refuses");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(matches!(
+            Daemon::bind(client(), dir.join("daemon.sock")),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::PermissionDenied
+        ));
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn closed_connections_drop_their_activities() {
+        let dir = dir("disconnect");
+        let path = dir.join("daemon.sock");
+        let daemon = std::sync::Arc::new(Daemon::bind(client(), &path).unwrap());
+        // The accept loop never returns, so the thread is left running.
+        let running = std::sync::Arc::clone(&daemon);
+        std::thread::spawn(move || running.run());
+
+        let displayed = || daemon.lock().displayed.clone();
+        let activity = |details: &str| Some(Activity::new().set_details(details.to_string()));
+        let mut low = DaemonClient::connect(&path).unwrap();
+        assert!(
+            low.set_activity("low", 0, activity("low").unwrap())
+                .unwrap()
+                .ok
+        );
+        let mut high = DaemonClient::connect(&path).unwrap();
+        assert!(
+            high.set_activity("high", 1, activity("high").unwrap())
+                .unwrap()
+                .ok
+        );
+        assert_eq!(displayed(), activity("high"));
+
+        drop(high);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while displayed() != activity("low") {
+            assert!(std::time::Instant::now() < deadline, "timed out");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        drop(low);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::activities::{Activity, redact_secrets};
+use crate::discord_connection::Client;
+use crate::error::Error;
+use serde_json::json;
+use std::sync::Arc;
+
+/// The well-known bus name of the presence service.
+pub const BUS_NAME: &str = "org.discord_presence_rs";
+
+/// The object path of the presence service.
+pub const OBJECT_PATH: &str = "/org/discord_presence_rs";
+
+/// The D-Bus interface exposing a `Client` to other processes.
+struct PresenceService {
+    client: Arc<Client>,
+}
+
+#[zbus::interface(name = "org.discord_presence_rs")]
+impl PresenceService {
+    /// Sets the activity from its JSON representation.
+    fn set_activity(&self, activity: &str) -> zbus::fdo::Result<()> {
+        let activity = Activity::from_json_str(activity).map_err(to_fdo_error)?;
+        self.client.set_activity(activity).map_err(to_fdo_error)
+    }
+
+    /// Clears the activity.
+    fn clear_activity(&self) -> zbus::fdo::Result<()> {
+        self.client.clear_activity().map_err(to_fdo_error)
+    }
+
+    /// Returns the client ID and current activity as JSON, with secrets redacted.
+    fn status(&self) -> zbus::fdo::Result<String> {
+        let mut activity = json!(self.client.last_activity());
+        redact_secrets(&mut activity);
+        Ok(json!({
+            "client_id": self.client.client_id.as_str(),
+            "activity": activity,
+        })
+        .to_string())
+    }
+}
+
+/// Converts a crate error into a D-Bus error.
+fn to_fdo_error(error: Error) -> zbus::fdo::Error {
+    zbus::fdo::Error::Failed(error.to_string())
+}
+
+/// Serves the presence service on the session bus.
+///
+/// The service runs on the connection's own thread and stops when the returned
+/// connection is dropped.
+pub fn serve(client: Arc<Client>) -> Result<zbus::blocking::Connection, Error> {
+    let service = PresenceService { client };
+    let connection = zbus::blocking::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, service)?
+        .build()?;
+    Ok(connection)
+}
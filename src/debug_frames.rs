@@ -0,0 +1,83 @@
+use crate::activities::SECRET_FIELDS;
+use crate::frame::Frame;
+use serde_json::Value;
+
+pub use crate::activities::REDACTED;
+
+/// The log target used for frame logging.
+pub const LOG_TARGET: &str = "discord_presence_rs::frames";
+
+/// Keys whose values are redacted wherever they appear.
+const TOKEN_KEYS: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "token",
+    "client_secret",
+    "code",
+];
+
+/// Represents the direction a frame travelled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// The frame was sent to Discord.
+    Sent,
+    /// The frame was received from Discord.
+    Received,
+}
+
+/// Logs a frame at debug level, with secrets and tokens redacted.
+///
+/// Payloads that are not JSON are logged by length only.
+pub fn log_frame(direction: Direction, frame: &Frame) {
+    if !log::log_enabled!(target: LOG_TARGET, log::Level::Debug) {
+        return;
+    }
+
+    let payload = match serde_json::from_slice::<Value>(&frame.payload) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(_) if frame.payload.is_empty() => String::new(),
+        Err(_) => "<non-JSON payload>".to_string(),
+    };
+    log::debug!(
+        target: LOG_TARGET,
+        "{:?} opcode={:?} len={} payload={}",
+        direction,
+        frame.opcode,
+        frame.payload.len(),
+        payload
+    );
+}
+
+/// Redacts activity secrets and OAuth tokens in a JSON payload.
+pub fn redact(value: &mut Value) {
+    redact_inner(value, false);
+}
+
+/// Redacts `value`, treating it as the contents of a `secrets` object if `in_secrets` is set.
+fn redact_inner(value: &mut Value, in_secrets: bool) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let is_secret = in_secrets && SECRET_FIELDS.contains(&key.as_str());
+                // Only string tokens are redacted, so numeric close codes stay readable.
+                let is_token = value.is_string() && TOKEN_KEYS.contains(&key.as_str());
+                if is_secret || is_token {
+                    if !value.is_null() {
+                        *value = Value::String(REDACTED.to_string());
+                    }
+                } else {
+                    redact_inner(value, key == "secrets");
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                redact_inner(value, false);
+            }
+        }
+        _ => {}
+    }
+}
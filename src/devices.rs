@@ -0,0 +1,319 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Represents a certified hardware device, as sent with `SET_CERTIFIED_DEVICES`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CertifiedDevice {
+    /// The kind of device.
+    #[serde(rename = "type")]
+    device_type: DeviceType,
+    /// The Windows ID of the device.
+    id: String,
+    /// The vendor of the device.
+    vendor: DeviceVendor,
+    /// The model of the device.
+    model: DeviceModel,
+    /// The IDs of related devices, such as the output of a headset.
+    related: Vec<String>,
+    /// Whether the device cancels echo in hardware. Audio inputs only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    echo_cancellation: Option<bool>,
+    /// Whether the device suppresses noise in hardware. Audio inputs only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    noise_suppression: Option<bool>,
+    /// Whether the device controls its gain in hardware. Audio inputs only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    automatic_gain_control: Option<bool>,
+    /// Whether the device is muted by a hardware switch. Audio inputs only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hardware_mute: Option<bool>,
+}
+
+/// Represents the kind of a certified device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceType {
+    /// A microphone.
+    AudioInput,
+    /// Speakers or headphones.
+    AudioOutput,
+    /// A camera.
+    VideoInput,
+}
+
+/// Represents the vendor of a certified device.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DeviceVendor {
+    /// The name of the vendor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// The URL of the vendor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+/// Represents the model of a certified device.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DeviceModel {
+    /// The name of the model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// The URL of the model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+impl CertifiedDevice {
+    /// Creates a new `CertifiedDevice` with its kind and Windows ID.
+    ///
+    /// The ID is either a UUID or an audio endpoint ID such as
+    /// `{0.0.1.00000000}.{6cff2b76-44a8-46b9-b528-262ad3609d7b}`.
+    pub fn new(device_type: DeviceType, id: String) -> Self {
+        CertifiedDevice {
+            device_type,
+            id,
+            vendor: DeviceVendor::new(),
+            model: DeviceModel::new(),
+            related: Vec::new(),
+            echo_cancellation: None,
+            noise_suppression: None,
+            automatic_gain_control: None,
+            hardware_mute: None,
+        }
+    }
+
+    /// Validates the device against Discord's constraints.
+    ///
+    /// The ID and every related ID must be UUIDs or audio endpoint IDs ending in one,
+    /// and the vendor and model must have a name.
+    pub fn validate(&self) -> Result<(), Error> {
+        for id in std::iter::once(&self.id).chain(&self.related) {
+            if !is_device_id(id) {
+                return Err(Error::InvalidDeviceId(id.clone()));
+            }
+        }
+        if self.vendor.name.as_deref().is_none_or(str::is_empty) {
+            return Err(Error::DeviceVendorMissing);
+        }
+        if self.model.name.as_deref().is_none_or(str::is_empty) {
+            return Err(Error::DeviceModelMissing);
+        }
+        Ok(())
+    }
+
+    /// Sets the vendor of the device.
+    pub fn set_vendor(mut self, vendor: DeviceVendor) -> Self {
+        self.vendor = vendor;
+        self
+    }
+
+    /// Sets the vendor of the device in place.
+    pub fn vendor_mut(&mut self, vendor: DeviceVendor) -> &mut Self {
+        self.vendor = vendor;
+        self
+    }
+
+    /// Sets the model of the device.
+    pub fn set_model(mut self, model: DeviceModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Sets the model of the device in place.
+    pub fn model_mut(&mut self, model: DeviceModel) -> &mut Self {
+        self.model = model;
+        self
+    }
+
+    /// Adds the ID of a related device.
+    pub fn add_related(mut self, id: String) -> Self {
+        self.related.push(id);
+        self
+    }
+
+    /// Adds the ID of a related device in place.
+    pub fn add_related_mut(&mut self, id: String) -> &mut Self {
+        self.related.push(id);
+        self
+    }
+
+    /// Sets whether the device cancels echo in hardware.
+    pub fn set_echo_cancellation(mut self, echo_cancellation: bool) -> Self {
+        self.echo_cancellation = Some(echo_cancellation);
+        self
+    }
+
+    /// Sets whether the device cancels echo in hardware in place.
+    pub fn echo_cancellation_mut(&mut self, echo_cancellation: bool) -> &mut Self {
+        self.echo_cancellation = Some(echo_cancellation);
+        self
+    }
+
+    /// Sets whether the device suppresses noise in hardware.
+    pub fn set_noise_suppression(mut self, noise_suppression: bool) -> Self {
+        self.noise_suppression = Some(noise_suppression);
+        self
+    }
+
+    /// Sets whether the device suppresses noise in hardware in place.
+    pub fn noise_suppression_mut(&mut self, noise_suppression: bool) -> &mut Self {
+        self.noise_suppression = Some(noise_suppression);
+        self
+    }
+
+    /// Sets whether the device controls its gain in hardware.
+    pub fn set_automatic_gain_control(mut self, automatic_gain_control: bool) -> Self {
+        self.automatic_gain_control = Some(automatic_gain_control);
+        self
+    }
+
+    /// Sets whether the device controls its gain in hardware in place.
+    pub fn automatic_gain_control_mut(&mut self, automatic_gain_control: bool) -> &mut Self {
+        self.automatic_gain_control = Some(automatic_gain_control);
+        self
+    }
+
+    /// Sets whether the device is muted by a hardware switch.
+    pub fn set_hardware_mute(mut self, hardware_mute: bool) -> Self {
+        self.hardware_mute = Some(hardware_mute);
+        self
+    }
+
+    /// Sets whether the device is muted by a hardware switch in place.
+    pub fn hardware_mute_mut(&mut self, hardware_mute: bool) -> &mut Self {
+        self.hardware_mute = Some(hardware_mute);
+        self
+    }
+}
+
+/// Returns whether `id` is a UUID or an audio endpoint ID ending in one.
+fn is_device_id(id: &str) -> bool {
+    // Audio endpoints are identified as `{<endpoint>}.{<GUID>}`.
+    let guid = match id.rsplit_once("}.{") {
+        Some((_, guid)) => guid.strip_suffix('}'),
+        None => Some(id),
+    };
+    guid.is_some_and(|guid| Uuid::parse_str(guid).is_ok())
+}
+
+impl DeviceVendor {
+    /// Creates a new `DeviceVendor`.
+    pub fn new() -> Self {
+        DeviceVendor {
+            name: None,
+            url: None,
+        }
+    }
+
+    /// Sets the name of the vendor.
+    pub fn set_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the name of the vendor in place.
+    pub fn name_mut(&mut self, name: String) -> &mut Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the URL of the vendor.
+    pub fn set_url(mut self, url: String) -> Self {
+        self.url = Some(url);
+        self
+    }
+
+    /// Sets the URL of the vendor in place.
+    pub fn url_mut(&mut self, url: String) -> &mut Self {
+        self.url = Some(url);
+        self
+    }
+}
+
+impl DeviceModel {
+    /// Creates a new `DeviceModel`.
+    pub fn new() -> Self {
+        DeviceModel {
+            name: None,
+            url: None,
+        }
+    }
+
+    /// Sets the name of the model.
+    pub fn set_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the name of the model in place.
+    pub fn name_mut(&mut self, name: String) -> &mut Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the URL of the model.
+    pub fn set_url(mut self, url: String) -> Self {
+        self.url = Some(url);
+        self
+    }
+
+    /// Sets the URL of the model in place.
+    pub fn url_mut(&mut self, url: String) -> &mut Self {
+        self.url = Some(url);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a device with a vendor and model, so only the IDs are checked.
+    fn device(id: &str) -> CertifiedDevice {
+        CertifiedDevice::new(DeviceType::AudioInput, id.to_string())
+            .set_vendor(DeviceVendor::new().set_name("Vendor".to_string()))
+            .set_model(DeviceModel::new().set_name("Model".to_string()))
+    }
+
+    #[test]
+    fn accepts_uuids_and_endpoint_ids() {
+        assert!(is_device_id("6cff2b76-44a8-46b9-b528-262ad3609d7b"));
+        assert!(is_device_id("{6cff2b76-44a8-46b9-b528-262ad3609d7b}"));
+        assert!(is_device_id(
+            "{0.0.1.00000000}.{6cff2b76-44a8-46b9-b528-262ad3609d7b}"
+        ));
+    }
+
+    #[test]
+    fn rejects_other_ids() {
+        assert!(!is_device_id(""));
+        assert!(!is_device_id("microphone"));
+        assert!(!is_device_id("{0.0.1.00000000}.{not-a-guid}"));
+        assert!(!is_device_id(
+            "{0.0.1.00000000}.{6cff2b76-44a8-46b9-b528-262ad3609d7b"
+        ));
+    }
+
+    #[test]
+    fn validate_checks_related_ids() {
+        let mut device = device("{0.0.1.00000000}.{6cff2b76-44a8-46b9-b528-262ad3609d7b}");
+        assert!(device.validate().is_ok());
+
+        device.add_related_mut("speakers".to_string());
+        assert!(matches!(
+            device.validate(),
+            Err(Error::InvalidDeviceId(id)) if id == "speakers"
+        ));
+    }
+
+    #[test]
+    fn validate_requires_a_vendor_and_model_name() {
+        let id = "6cff2b76-44a8-46b9-b528-262ad3609d7b".to_string();
+        let device = CertifiedDevice::new(DeviceType::VideoInput, id);
+        assert!(matches!(device.validate(), Err(Error::DeviceVendorMissing)));
+
+        let device = device.set_vendor(DeviceVendor::new().set_name("Vendor".to_string()));
+        assert!(matches!(device.validate(), Err(Error::DeviceModelMissing)));
+    }
+}
@@ -1,194 +1,1459 @@
 use crate::activities::Activity;
 use crate::error::Error;
+use crate::frame::{
+    DEFAULT_MAX_FRAME_SIZE, Frame, MAX_SEND_FRAME_SIZE, Opcode, Utf8Policy, encoded_len, wipe,
+};
+use crate::models::User;
+use crate::platform::{Discovery, PlatformIpc, Probe};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::io::{Read, Write};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 
-/// A trait for Inter-Process Communication (IPC).
-pub trait Ipc: Read + Write + Send + Sync {
-    /// Connects to the IPC server.
-    fn connect() -> Result<Self, Error>
-    where
-        Self: Sized;
-}
+/// The default version of the IPC protocol requested in the handshake.
+pub const DEFAULT_HANDSHAKE_VERSION: u32 = 1;
 
-/// A struct for Unix IPC.
-#[cfg(unix)]
-pub struct UnixIpc(std::os::unix::net::UnixStream);
+/// The default time to wait for Discord to answer the handshake.
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The default number of applied activities kept in the history of a client.
+pub const DEFAULT_HISTORY_LEN: usize = 16;
 
+/// The environment variable that disables every client when set to `1` or `true`.
+pub const DISABLED_ENV_VAR: &str = "DISCORD_PRESENCE_DISABLED";
+
+pub use crate::platform::Ipc;
 #[cfg(unix)]
-impl Ipc for UnixIpc {
-    /// Connects to the Discord IPC server on Unix.
-    fn connect() -> Result<Self, Error> {
-        let path = std::env::var("XDG_RUNTIME_DIR")
-            .or_else(|_| std::env::var("TMPDIR"))
-            .map(std::path::PathBuf::from)
-            .unwrap();
+pub use crate::platform::UnixIpc;
+#[cfg(not(any(unix, windows)))]
+pub use crate::platform::UnsupportedIpc;
+#[cfg(windows)]
+pub use crate::platform::WindowsIpc;
 
-        for i in 0..10 {
-            let sock_path = path.join(format!("discord-ipc-{}", i));
-            if sock_path.exists() {
-                let stream = std::os::unix::net::UnixStream::connect(sock_path)?;
-                return Ok(Self(stream));
-            }
+/// Represents the client ID of a Discord application.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ClientId(String);
+
+impl ClientId {
+    /// Returns the client ID as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Validates that the client ID is a numeric snowflake.
+    pub fn validate(&self) -> Result<(), Error> {
+        let is_numeric = !self.0.is_empty() && self.0.bytes().all(|b| b.is_ascii_digit());
+        if is_numeric && self.0.parse::<u64>().is_ok() {
+            Ok(())
+        } else {
+            Err(Error::InvalidClientId)
         }
-        Err(Error::ConnectionNotFound)
     }
 }
 
-#[cfg(unix)]
-impl Read for UnixIpc {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.0.read(buf)
+impl From<u64> for ClientId {
+    fn from(client_id: u64) -> Self {
+        ClientId(client_id.to_string())
     }
 }
 
-#[cfg(unix)]
-impl Write for UnixIpc {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0.write(buf)
+impl From<&str> for ClientId {
+    fn from(client_id: &str) -> Self {
+        ClientId(client_id.to_string())
     }
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.0.flush()
+}
+
+impl From<String> for ClientId {
+    fn from(client_id: String) -> Self {
+        ClientId(client_id)
     }
 }
 
-/// A struct for Windows IPC.
-#[cfg(windows)]
-pub struct WindowsIpc(std::fs::File);
+impl std::fmt::Display for ClientId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
 
-#[cfg(windows)]
-impl WindowsIpc {
-    fn new(file: std::fs::File) -> Self {
-        Self(file)
+impl Serialize for ClientId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
     }
 }
 
-#[cfg(windows)]
-impl Ipc for WindowsIpc {
-    /// Connects to the Discord IPC server on Windows.
-    fn connect() -> Result<Self, Error> {
-        for i in 0..10 {
-            let path = format!(r"\\.\pipe\discord-ipc-{}", i);
-            match std::fs::OpenOptions::new()
-                .read(true)
-                .write(true)
-                .open(path)
-            {
-                Ok(file) => return Ok(Self(file)),
-                Err(_) => continue,
-            }
+impl<'de> Deserialize<'de> for ClientId {
+    /// Deserializes a client ID from either a string or an integer.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            String(String),
+            Integer(u64),
         }
-        Err(Error::ConnectionNotFound)
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::String(client_id) => ClientId(client_id),
+            Repr::Integer(client_id) => ClientId::from(client_id),
+        })
     }
 }
 
-#[cfg(windows)]
-impl Read for WindowsIpc {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.0.read(buf)
-    }
+/// Represents the data Discord sends in the READY event after the handshake.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ready {
+    /// The protocol version negotiated with Discord.
+    #[serde(rename = "v")]
+    pub version: u32,
+    /// The configuration of the Discord client.
+    pub config: ReadyConfig,
+    /// The connected user, if Discord sent one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<User>,
 }
 
-#[cfg(windows)]
-impl Write for WindowsIpc {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0.write(buf)
-    }
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.0.flush()
-    }
+/// Represents the configuration of the Discord client, sent in the READY event.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadyConfig {
+    /// The host serving images, such as avatars.
+    pub cdn_host: String,
+    /// The endpoint of the HTTP API.
+    pub api_endpoint: String,
+    /// The release environment of the Discord client.
+    pub environment: String,
 }
 
 /// The main client for interacting with the Discord Gateway.
+///
+/// Every command takes `&self`, so a `Client` can be shared between threads behind an
+/// `Arc` without further locking. Commands from different threads are serialized.
 pub struct Client {
-    ipc: Box<dyn Ipc>,
     /// The client ID of the application.
-    pub client_id: String,
+    pub client_id: ClientId,
+    auto_elapsed: bool,
+    /// Whether commands are sent, or succeed without doing anything.
+    enabled: AtomicBool,
+    /// Whether commands are validated and serialized but never sent.
+    dry_run: bool,
+    /// The configuration the client was built from, used to reconnect.
+    builder: ClientBuilder,
+    state: Mutex<State>,
 }
 
-impl Client {
-    /// Creates a new `Client`.
-    pub fn new(client_id: &str) -> Result<Self, Error> {
-        #[cfg(unix)]
-        let ipc = Box::new(UnixIpc::connect()?);
-        #[cfg(windows)]
-        let ipc = Box::new(WindowsIpc::connect()?);
-        #[cfg(not(any(unix, windows)))]
-        return Err(Error::ConnectionNotFound);
+/// Represents an activity in the history of a `Client`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// The activity that was applied.
+    pub activity: Activity,
+    /// When the activity was applied.
+    pub applied_at: SystemTime,
+}
+
+/// The mutable state of a `Client`, guarded by its lock.
+struct State {
+    /// The connection to Discord, or `None` if the client is disabled or in dry-run mode.
+    ipc: Option<Box<dyn Ipc>>,
+    /// Whether frames are built without being sent, see `ClientBuilder::set_dry_run`.
+    dry_run: bool,
+    /// The unsent remainder of a frame that was partially written without blocking.
+    pending: Vec<u8>,
+    /// The most recently applied activity.
+    last_activity: Option<Activity>,
+    /// The most recently applied activities, oldest first.
+    history: VecDeque<HistoryEntry>,
+    /// The activities saved by `push_activity`, innermost last.
+    stack: Vec<Option<Activity>>,
+    /// The start time of the current session, in Unix milliseconds.
+    session_start: Option<u64>,
+    /// The data received in the READY event of the current connection.
+    ready: Ready,
+    /// Bytes received without blocking that do not form a complete frame yet.
+    received: Vec<u8>,
+    /// An error hit by `poll_events` after it had collected messages, reported by the
+    /// next call.
+    deferred: Option<Error>,
+}
+
+/// A builder for configuring and connecting a `Client`.
+#[derive(Clone, Debug)]
+pub struct ClientBuilder {
+    client_id: ClientId,
+    max_frame_size: u32,
+    utf8_policy: Utf8Policy,
+    discovery: Discovery,
+    auto_elapsed: bool,
+    handshake_version: u32,
+    handshake_fields: serde_json::Map<String, serde_json::Value>,
+    handshake_timeout: Duration,
+    remove_stale_sockets: bool,
+    enabled: bool,
+    dry_run: bool,
+    truncate_text: bool,
+    history_len: usize,
+    validate_json: bool,
+    #[cfg(target_os = "linux")]
+    wsl_bridge_path: Option<PathBuf>,
+}
+
+impl ClientBuilder {
+    /// Creates a new `ClientBuilder`.
+    pub fn new(client_id: impl Into<ClientId>) -> Self {
+        ClientBuilder {
+            client_id: client_id.into(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            utf8_policy: Utf8Policy::default(),
+            discovery: Discovery::new(),
+            auto_elapsed: false,
+            handshake_version: DEFAULT_HANDSHAKE_VERSION,
+            handshake_fields: serde_json::Map::new(),
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            remove_stale_sockets: false,
+            enabled: true,
+            dry_run: false,
+            truncate_text: false,
+            history_len: DEFAULT_HISTORY_LEN,
+            validate_json: false,
+            #[cfg(target_os = "linux")]
+            wsl_bridge_path: None,
+        }
+    }
 
-        let mut client = Self {
-            ipc,
-            client_id: client_id.to_string(),
+    /// Sets the maximum payload size accepted in a single frame from Discord.
+    pub fn set_max_frame_size(mut self, max_frame_size: u32) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Sets how invalid UTF-8 in frames received from Discord is handled.
+    pub fn set_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.utf8_policy = utf8_policy;
+        self
+    }
+
+    /// Sets where to look for the Discord IPC socket.
+    pub fn set_discovery(mut self, discovery: Discovery) -> Self {
+        self.discovery = discovery;
+        self
+    }
+
+    /// Sets whether activities without a start time are stamped with the session start.
+    ///
+    /// The session starts with the first activity that is set and lasts until the
+    /// activity is cleared, so the elapsed counter keeps running across updates.
+    pub fn set_auto_elapsed(mut self, auto_elapsed: bool) -> Self {
+        self.auto_elapsed = auto_elapsed;
+        self
+    }
+
+    /// Sets the version of the IPC protocol requested in the handshake.
+    pub fn set_handshake_version(mut self, handshake_version: u32) -> Self {
+        self.handshake_version = handshake_version;
+        self
+    }
+
+    /// Adds a field to the handshake payload.
+    ///
+    /// The `v` and `client_id` fields are always set from the builder and cannot be
+    /// overridden this way.
+    pub fn add_handshake_field(mut self, key: String, value: serde_json::Value) -> Self {
+        self.handshake_fields.insert(key, value);
+        self
+    }
+
+    /// Sets how long to wait for Discord to answer the handshake.
+    ///
+    /// A candidate that does not answer in time is skipped. Transports without read
+    /// timeouts wait indefinitely.
+    pub fn set_handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        self
+    }
+
+    /// Sets whether stale sockets are removed before connecting.
+    ///
+    /// See `Discovery::remove_stale_sockets`.
+    pub fn set_remove_stale_sockets(mut self, remove_stale_sockets: bool) -> Self {
+        self.remove_stale_sockets = remove_stale_sockets;
+        self
+    }
+
+    /// Sets whether the client is enabled.
+    ///
+    /// A disabled client does not connect to Discord until it is enabled with
+    /// `Client::set_enabled`, and every command succeeds without doing anything. Setting
+    /// the `DISCORD_PRESENCE_DISABLED` environment variable to `1` or `true` disables
+    /// every client regardless of this setting.
+    pub fn set_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Sets whether the client runs in dry-run mode.
+    ///
+    /// A dry-run client never connects to Discord. Commands are validated and
+    /// serialized as usual, and the frames are logged with the `debug-frames` feature,
+    /// but nothing is sent. Useful for testing integrations without Discord installed.
+    pub fn set_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Sets whether over-long texts are truncated before activities are sent.
+    ///
+    /// When enabled, `set_activity` and `try_set_activity` pass every activity through
+    /// `Activity::truncate_text`, so titles longer than Discord allows are cut with an
+    /// ellipsis. Disabled by default.
+    pub fn set_truncate_text(mut self, truncate_text: bool) -> Self {
+        self.truncate_text = truncate_text;
+        self
+    }
+
+    /// Sets how many applied activities are kept in the history of the client.
+    ///
+    /// Once the history is full, the oldest entry is dropped for each new one. Set to `0`
+    /// to keep no history. Defaults to `DEFAULT_HISTORY_LEN`.
+    pub fn set_history_len(mut self, history_len: usize) -> Self {
+        self.history_len = history_len;
+        self
+    }
+
+    /// Sets whether raw activities are checked against the SET_ACTIVITY schema.
+    ///
+    /// When enabled, `set_activity_json` rejects activities that fail
+    /// `schema::validate_activity` instead of sending them. Disabled by default.
+    pub fn set_validate_json(mut self, validate_json: bool) -> Self {
+        self.validate_json = validate_json;
+        self
+    }
+
+    /// Returns every socket path that is tried when connecting, in order.
+    pub fn candidates(&self) -> Vec<PathBuf> {
+        #[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
+        let mut candidates = Vec::new();
+
+        #[cfg(target_os = "linux")]
+        if let Some(path) = &self.wsl_bridge_path
+            && crate::platform::is_wsl()
+        {
+            candidates.push(path.clone());
+        }
+
+        candidates.extend(self.discovery.candidates());
+        candidates
+    }
+
+    /// Probes every candidate socket path and reports what was found at each one.
+    ///
+    /// Useful for triaging `Error::ConnectionNotFound`. See `Discovery::diagnose` for
+    /// what a probe does on each platform; on Windows, it opens each pipe.
+    pub fn diagnose(&self) -> Vec<Probe> {
+        crate::platform::diagnose_paths(self.candidates())
+    }
+
+    /// Sets the socket of a bridge to the Windows Discord client, used under WSL.
+    ///
+    /// A bridge is typically created with `socat` and `npiperelay`:
+    ///
+    /// ```text
+    /// socat UNIX-LISTEN:/tmp/discord-ipc-0,fork EXEC:"npiperelay.exe //./pipe/discord-ipc-0"
+    /// ```
+    ///
+    /// Sockets created at that default location are found without configuration.
+    #[cfg(target_os = "linux")]
+    pub fn set_wsl_bridge_path(mut self, wsl_bridge_path: PathBuf) -> Self {
+        self.wsl_bridge_path = Some(wsl_bridge_path);
+        self
+    }
+
+    /// Sets the options used to open the Discord IPC pipe on Windows.
+    ///
+    /// This sets them on the discovery, so a later `set_discovery` replaces them. See
+    /// `Discovery::set_pipe_options`.
+    #[cfg(windows)]
+    pub fn set_pipe_options(mut self, pipe_options: crate::platform::PipeOptions) -> Self {
+        self.discovery = self.discovery.set_pipe_options(pipe_options);
+        self
+    }
+
+    /// Connects to Discord and performs the handshake.
+    ///
+    /// If the client is disabled or in dry-run mode, no connection is made. The client
+    /// ID of a disabled client is only validated once it is enabled.
+    pub fn build(self) -> Result<Client, Error> {
+        let enabled = self.enabled && !disabled_by_env();
+        if enabled {
+            self.client_id.validate()?;
+        }
+        let connect = enabled && !self.dry_run;
+        let (ipc, ready) = if connect {
+            let (ipc, ready) = self.connect()?;
+            (Some(ipc), ready)
+        } else {
+            (None, Ready::default())
         };
 
-        client.handshake()?;
+        let dry_run = self.dry_run;
+        let client = Client {
+            client_id: self.client_id.clone(),
+            auto_elapsed: self.auto_elapsed,
+            enabled: AtomicBool::new(enabled),
+            dry_run,
+            builder: self,
+            state: Mutex::new(State {
+                ipc,
+                dry_run,
+                pending: Vec::new(),
+                last_activity: None,
+                history: VecDeque::new(),
+                stack: Vec::new(),
+                session_start: None,
+                ready,
+                received: Vec::new(),
+                deferred: None,
+            }),
+        };
         Ok(client)
     }
 
-    /// Performs the handshake with the Discord IPC server.
-    fn handshake(&mut self) -> Result<(), Error> {
-        let payload = json!({
-            "v": 1,
-            "client_id": self.client_id
-        });
-        self.write_ipc(0, payload.to_string())?;
+    /// Connects to the first candidate that completes the handshake.
+    ///
+    /// A candidate that accepts the connection but fails the handshake, such as a stale
+    /// socket left by a crashed Discord, is skipped. If every candidate fails, the last
+    /// error other than nothing listening is returned, or `Error::ConnectionNotFound`.
+    /// On targets without a transport, `Error::UnsupportedPlatform` is returned instead.
+    fn connect(&self) -> Result<(Box<dyn Ipc>, Ready), Error> {
+        if self.remove_stale_sockets {
+            self.discovery.remove_stale_sockets();
+        }
+
+        // Discovery finds no candidates on targets without a transport.
+        #[cfg(not(any(unix, windows)))]
+        let mut last_error = Error::UnsupportedPlatform;
+        #[cfg(any(unix, windows))]
+        let mut last_error = Error::ConnectionNotFound;
+        for path in self.candidates() {
+            #[cfg(windows)]
+            let ipc = PlatformIpc::connect_path_with(&path, self.discovery.pipe_options());
+            #[cfg(not(windows))]
+            let ipc = PlatformIpc::connect_path(&path);
+            let mut ipc: Box<dyn Ipc> = match ipc {
+                Ok(ipc) => Box::new(ipc),
+                // Nothing is listening at the path.
+                Err(Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => {
+                    last_error = e;
+                    continue;
+                }
+            };
+            match self.handshake(ipc.as_mut()) {
+                Ok(ready) => return Ok((ipc, ready)),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Performs the handshake over `ipc` and returns the READY data.
+    fn handshake(&self, ipc: &mut dyn Ipc) -> Result<Ready, Error> {
+        let mut payload = self.handshake_fields.clone();
+        payload.insert("v".to_string(), json!(self.handshake_version));
+        payload.insert("client_id".to_string(), json!(self.client_id.as_str()));
+        let frame = Frame::new(
+            Opcode::Handshake,
+            serde_json::Value::Object(payload).to_string().into_bytes(),
+        );
+        #[cfg(feature = "debug-frames")]
+        crate::debug_frames::log_frame(crate::debug_frames::Direction::Sent, &frame);
+        frame.write_to(ipc)?;
 
-        let response = self.read_ipc()?;
-        let response_data: serde_json::Value = serde_json::from_str(&response)?;
+        // Transports without read timeouts wait indefinitely.
+        let _ = ipc.set_read_timeout(Some(self.handshake_timeout));
+        let response = Frame::read_from(ipc, self.max_frame_size);
+        let _ = ipc.set_read_timeout(None);
+        let response = response?;
+        #[cfg(feature = "debug-frames")]
+        crate::debug_frames::log_frame(crate::debug_frames::Direction::Received, &response);
+        if let Some(error) = response.close_error() {
+            return Err(error);
+        }
+
+        let response = response.into_string(self.utf8_policy)?;
+        let mut response_data: serde_json::Value = serde_json::from_str(&response)?;
 
         if response_data["cmd"].as_str() == Some("DISPATCH")
             && response_data["evt"].as_str() == Some("READY")
         {
-            Ok(())
+            let ready: Option<Ready> = serde_json::from_value(response_data["data"].take())?;
+            Ok(ready.unwrap_or_default())
         } else {
             Err(Error::HandshakeFailed)
         }
     }
+}
+
+impl Client {
+    /// Creates a new `Client`.
+    pub fn new(client_id: impl Into<ClientId>) -> Result<Self, Error> {
+        ClientBuilder::new(client_id).build()
+    }
+
+    /// Creates a new `ClientBuilder`.
+    pub fn builder(client_id: impl Into<ClientId>) -> ClientBuilder {
+        ClientBuilder::new(client_id)
+    }
+
+    /// Probes the default socket paths and reports what was found at each one.
+    ///
+    /// See `Discovery::diagnose` for what a probe does on each platform; on Windows,
+    /// it opens each pipe.
+    pub fn diagnose() -> Vec<Probe> {
+        Discovery::new().diagnose()
+    }
+
+    /// Returns whether the client is enabled.
+    ///
+    /// See `ClientBuilder::set_enabled` and `set_enabled`.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Enables or disables the client at runtime, such as from a "show my presence"
+    /// setting.
+    ///
+    /// Disabling clears the activity, and later commands succeed without doing anything.
+    /// The client is disabled even if clearing fails. Enabling validates the client ID
+    /// and connects to Discord if the client has no connection yet. While the
+    /// `DISCORD_PRESENCE_DISABLED` environment variable is set, the client stays
+    /// disabled.
+    pub fn set_enabled(&self, enabled: bool) -> Result<(), Error> {
+        let mut state = self.lock();
+        if enabled == self.is_enabled() {
+            return Ok(());
+        }
+
+        if !enabled {
+            self.enabled.store(false, Ordering::SeqCst);
+            state.write_ipc(Opcode::Frame, Self::clear_payload())?;
+            state.last_activity = None;
+            state.session_start = None;
+            return Ok(());
+        }
+
+        if disabled_by_env() {
+            return Ok(());
+        }
+        self.client_id.validate()?;
+        if !self.dry_run && state.ipc.is_none() {
+            let (ipc, ready) = self.builder.connect()?;
+            state.ipc = Some(ipc);
+            state.ready = ready;
+            state.pending.clear();
+            state.received.clear();
+            state.deferred = None;
+        }
+        self.enabled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Returns whether the client runs in dry-run mode.
+    ///
+    /// See `ClientBuilder::set_dry_run`.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Returns the raw file descriptor of the connection, for registering it with an
+    /// event loop.
+    ///
+    /// Returns `None` if the client is not connected. The descriptor changes when the
+    /// client reconnects.
+    #[cfg(unix)]
+    pub fn raw_fd(&self) -> Option<std::os::fd::RawFd> {
+        self.lock().ipc.as_ref().and_then(|ipc| ipc.raw_fd())
+    }
+
+    /// Returns the raw handle of the connection, for registering it with an event loop.
+    ///
+    /// Returns `None` if the client is not connected. The handle changes when the client
+    /// reconnects.
+    #[cfg(windows)]
+    pub fn raw_handle(&self) -> Option<std::os::windows::io::RawHandle> {
+        self.lock().ipc.as_ref().and_then(|ipc| ipc.raw_handle())
+    }
+
+    /// Locks the client state, recovering it if another thread panicked while holding it.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Reconnects to Discord, running discovery again from scratch.
+    ///
+    /// A restarted Discord often listens on a different socket index, so the previous
+    /// path is not reused. After the handshake, the last activity is sent again.
+    pub fn reconnect(&self) -> Result<(), Error> {
+        if !self.is_enabled() || self.dry_run {
+            return Ok(());
+        }
+        let (ipc, ready) = self.builder.connect()?;
+        let mut state = self.lock();
+        state.ipc = Some(ipc);
+        state.ready = ready;
+        state.pending.clear();
+        state.received.clear();
+        state.deferred = None;
+
+        if let Some(activity) = &state.last_activity {
+            let payload = Self::activity_payload(activity)?;
+            state.write_ipc(Opcode::Frame, payload)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the data received in the READY event of the current connection.
+    pub fn ready(&self) -> Ready {
+        self.lock().ready.clone()
+    }
 
     /// Sets the activity for the user.
-    pub fn set_activity(&mut self, activity: Activity) -> Result<(), Error> {
-        let payload = json!({
+    pub fn set_activity(&self, activity: Activity) -> Result<(), Error> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let activity = self.prepare(activity);
+        activity.validate()?;
+        let mut state = self.lock();
+        self.send_activity(&mut state, activity)
+    }
+
+    /// Sends a validated activity and records it as applied.
+    fn send_activity(&self, state: &mut State, activity: Activity) -> Result<(), Error> {
+        let activity = self.stamp_elapsed(state, activity);
+        let payload = Self::activity_payload(&activity)?;
+        state.write_ipc(Opcode::Frame, payload)?;
+        state.record(activity, self.builder.history_len);
+        Ok(())
+    }
+
+    /// Returns the most recently applied activity, or `None` if it was cleared.
+    pub fn last_activity(&self) -> Option<Activity> {
+        self.lock().last_activity.clone()
+    }
+
+    /// Returns the most recently applied activities with the time each was applied,
+    /// oldest first.
+    ///
+    /// See `ClientBuilder::set_history_len`.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.lock().history.iter().cloned().collect()
+    }
+
+    /// Sets the activity for the user without blocking.
+    ///
+    /// Returns `Error::WouldBlock` if the connection cannot accept the update right
+    /// now, in which case nothing is sent. If the frame can only be written in part,
+    /// the remainder is sent before the next frame. Transports without non-blocking
+    /// support return an `Unsupported` I/O error.
+    pub fn try_set_activity(&self, activity: Activity) -> Result<(), Error> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let activity = self.prepare(activity);
+        activity.validate()?;
+        let mut state = self.lock();
+        let activity = self.stamp_elapsed(&mut state, activity);
+        let mut frame = Frame::new(
+            Opcode::Frame,
+            Self::activity_payload(&activity)?.into_bytes(),
+        );
+        if let Err(e) = frame.check_send_size() {
+            wipe(&mut frame.payload);
+            return Err(e);
+        }
+        let mut bytes = frame.encode()?;
+        #[cfg(feature = "debug-frames")]
+        crate::debug_frames::log_frame(crate::debug_frames::Direction::Sent, &frame);
+        wipe(&mut frame.payload);
+
+        if self.dry_run {
+            wipe(&mut bytes);
+        } else {
+            state.ipc()?.set_nonblocking(true)?;
+            let result = state.try_write(bytes);
+            state.ipc()?.set_nonblocking(false)?;
+            result?;
+        }
+        state.record(activity, self.builder.history_len);
+        Ok(())
+    }
+
+    /// Truncates the texts of an activity, if enabled.
+    fn prepare(&self, activity: Activity) -> Activity {
+        if self.builder.truncate_text {
+            return activity.truncate_text();
+        }
+        activity
+    }
+
+    /// Stamps the session start onto an activity without a start time, if enabled.
+    fn stamp_elapsed(&self, state: &mut State, activity: Activity) -> Activity {
+        if !self.auto_elapsed {
+            return activity;
+        }
+        let timestamps = activity.timestamps().cloned().unwrap_or_default();
+        if timestamps.start().is_some() {
+            return activity;
+        }
+
+        let start = *state.session_start.get_or_insert_with(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64
+        });
+        activity.set_timestamps(timestamps.set_start(start))
+    }
+
+    /// Builds the SET_ACTIVITY payload for an activity.
+    ///
+    /// The payload is serialized straight into a buffer sized for the largest frame
+    /// Discord accepts, so no partial copies of the secrets are left behind by
+    /// intermediate values or reallocation.
+    fn activity_payload(activity: &impl Serialize) -> Result<String, Error> {
+        #[derive(Serialize)]
+        struct Command<'a, T> {
+            cmd: &'static str,
+            args: Args<'a, T>,
+            nonce: String,
+        }
+
+        #[derive(Serialize)]
+        struct Args<'a, T> {
+            pid: u32,
+            activity: &'a T,
+        }
+
+        let command = Command {
+            cmd: "SET_ACTIVITY",
+            args: Args {
+                pid: std::process::id(),
+                activity,
+            },
+            nonce: Uuid::new_v4().to_string(),
+        };
+        let mut buf = Vec::with_capacity(MAX_SEND_FRAME_SIZE);
+        if let Err(e) = serde_json::to_writer(&mut buf, &command) {
+            wipe(&mut buf);
+            return Err(e.into());
+        }
+        Ok(String::from_utf8(buf).expect("serde_json writes UTF-8"))
+    }
+
+    /// Sets the activity for the user from raw JSON.
+    ///
+    /// `activity` is sent as the `activity` argument of SET_ACTIVITY without going
+    /// through the typed model, so fields Discord added after this crate can be used
+    /// right away. Nothing is truncated or stamped, and the activity is only validated if
+    /// `ClientBuilder::set_validate_json` is enabled. Since the activity cannot be
+    /// tracked, `last_activity` returns `None` afterwards and it is not sent again by
+    /// `reconnect`.
+    pub fn set_activity_json(&self, activity: &serde_json::Value) -> Result<(), Error> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if self.builder.validate_json {
+            crate::schema::validate_activity(activity)?;
+        }
+        let mut state = self.lock();
+        state.write_ipc(Opcode::Frame, Self::activity_payload(activity)?)?;
+        state.last_activity = None;
+        Ok(())
+    }
+
+    /// Clears the activity for the user.
+    pub fn clear_activity(&self) -> Result<(), Error> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let mut state = self.lock();
+        state.write_ipc(Opcode::Frame, Self::clear_payload())?;
+        state.last_activity = None;
+        state.session_start = None;
+        Ok(())
+    }
+
+    /// Builds the SET_ACTIVITY payload that clears the activity.
+    fn clear_payload() -> String {
+        json!({
             "cmd": "SET_ACTIVITY",
             "args": {
-                "pid": std::process::id(),
-                "activity": activity
+                "pid": std::process::id()
             },
             "nonce": Uuid::new_v4().to_string()
-        });
-        self.write_ipc(1, payload.to_string())?;
-        Ok(())
+        })
+        .to_string()
     }
 
-    /// Writes a message to the Discord IPC server.
-    fn write_ipc(&mut self, opcode: u32, payload: String) -> Result<(), Error> {
-        let payload_bytes = payload.as_bytes();
-        let len = payload_bytes.len() as u32;
+    /// Restores the activity that was applied before the most recent one.
+    ///
+    /// The most recent entry is removed from the history and the one before it is sent
+    /// again, timestamps included. If the history holds no earlier activity, the activity
+    /// is cleared instead. Returns the restored activity.
+    pub fn revert(&self) -> Result<Option<Activity>, Error> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let mut state = self.lock();
+        let previous = state
+            .history
+            .iter()
+            .rev()
+            .nth(1)
+            .map(|entry| entry.activity.clone());
+        match &previous {
+            Some(activity) => state.write_ipc(Opcode::Frame, Self::activity_payload(activity)?)?,
+            None => {
+                state.write_ipc(Opcode::Frame, Self::clear_payload())?;
+                state.session_start = None;
+            }
+        }
+        state.history.pop_back();
+        state.last_activity = previous.clone();
+        Ok(previous)
+    }
 
-        self.ipc.write_all(&opcode.to_le_bytes())?;
-        self.ipc.write_all(&len.to_le_bytes())?;
-        self.ipc.write_all(payload_bytes)?;
+    /// Sets a temporary activity, saving the current one so `pop_activity` can restore it.
+    ///
+    /// Pushes can be nested, and each `pop_activity` unwinds one of them.
+    pub fn push_activity(&self, activity: Activity) -> Result<(), Error> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let activity = self.prepare(activity);
+        activity.validate()?;
+        let mut state = self.lock();
+        let saved = state.last_activity.clone();
+        self.send_activity(&mut state, activity)?;
+        state.stack.push(saved);
         Ok(())
     }
 
-    /// Reads a message from the Discord IPC server.
-    fn read_ipc(&mut self) -> Result<String, Error> {
-        let mut opcode_buf = [0u8; 4];
-        let mut len_buf = [0u8; 4];
+    /// Restores the activity saved by the most recent `push_activity`.
+    ///
+    /// If no activity was showing at that push, the activity is cleared. Returns the
+    /// restored activity. Does nothing if no push is left to unwind.
+    pub fn pop_activity(&self) -> Result<Option<Activity>, Error> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let mut state = self.lock();
+        let Some(saved) = state.stack.last().cloned() else {
+            return Ok(None);
+        };
+        match &saved {
+            Some(activity) => self.send_activity(&mut state, activity.clone())?,
+            None => {
+                state.write_ipc(Opcode::Frame, Self::clear_payload())?;
+                state.last_activity = None;
+                state.session_start = None;
+            }
+        }
+        state.stack.pop();
+        Ok(saved)
+    }
 
-        self.ipc.read_exact(&mut opcode_buf)?;
-        self.ipc.read_exact(&mut len_buf)?;
+    /// Returns every message from Discord that has already arrived, without blocking.
+    ///
+    /// Messages are the JSON payloads of command responses and dispatched events, in
+    /// the order they arrived. A frame that has only partly arrived is kept for the next
+    /// call, and pings are answered along the way. If Discord closed the connection,
+    /// `Error::ClosedByServer` is returned. Transports without non-blocking support
+    /// return an `Unsupported` I/O error.
+    ///
+    /// A frame that cannot be parsed is discarded. If messages were collected before
+    /// it, they are returned first and the error is returned by the next call. A frame
+    /// too large to skip leaves the stream unframed, so the connection is dropped and
+    /// later commands return `Error::ConnectionNotFound` until `reconnect` succeeds.
+    pub fn poll_events(&self) -> Result<Vec<serde_json::Value>, Error> {
+        if !self.is_enabled() || self.dry_run {
+            return Ok(Vec::new());
+        }
+        let mut state = self.lock();
+        if let Some(error) = state.deferred.take() {
+            return Err(error);
+        }
+        state.ipc()?.set_nonblocking(true)?;
+        let result = state.read_available();
+        state.ipc()?.set_nonblocking(false)?;
+        let ended = result?;
+
+        let mut events = Vec::new();
+        loop {
+            let (frame, len) = match Frame::decode(&state.received, self.builder.max_frame_size) {
+                Ok(Some(decoded)) => decoded,
+                Ok(None) => break,
+                Err(error @ Error::InvalidOpcode(_)) => {
+                    let len = encoded_len(&state.received);
+                    state.received.drain(..len);
+                    return state.defer(events, error);
+                }
+                Err(error) => {
+                    // The stream can no longer be split into frames, so the connection is
+                    // dropped.
+                    state.received.clear();
+                    state.ipc = None;
+                    return state.defer(events, error);
+                }
+            };
+            if let Some(error) = frame.close_error() {
+                // Keep the close frame so the events before it are returned first.
+                if events.is_empty() {
+                    state.received.drain(..len);
+                    return Err(error);
+                }
+                return Ok(events);
+            }
+            state.received.drain(..len);
+            #[cfg(feature = "debug-frames")]
+            crate::debug_frames::log_frame(crate::debug_frames::Direction::Received, &frame);
 
-        let len = u32::from_le_bytes(len_buf);
-        let mut payload_buf = vec![0u8; len as usize];
-        self.ipc.read_exact(&mut payload_buf)?;
+            let result = match frame.opcode {
+                Opcode::Frame => frame
+                    .into_string(self.builder.utf8_policy)
+                    .and_then(|payload| Ok(serde_json::from_str(&payload)?))
+                    .map(|event| events.push(event)),
+                Opcode::Ping => frame
+                    .into_string(self.builder.utf8_policy)
+                    .and_then(|payload| state.write_ipc(Opcode::Pong, payload)),
+                Opcode::Handshake | Opcode::Pong | Opcode::Close => Ok(()),
+            };
+            if let Err(error) = result {
+                return state.defer(events, error);
+            }
+        }
 
-        Ok(String::from_utf8_lossy(&payload_buf).to_string())
+        if ended && events.is_empty() {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(events)
+    }
+
+    /// Consumes the client and returns its connection to Discord.
+    ///
+    /// Any pending partial write is flushed first and the connection is left in blocking
+    /// mode. Bytes already read by `poll_events` but not yet returned as messages are
+    /// discarded. Returns `Error::ConnectionNotFound` if the client is not connected.
+    pub fn into_inner(self) -> Result<Box<dyn Ipc>, Error> {
+        let mut state = self.state.into_inner().unwrap_or_else(|e| e.into_inner());
+        let mut ipc = state.ipc.take().ok_or(Error::ConnectionNotFound)?;
+        ipc.set_nonblocking(false).or_else(ignore_unsupported)?;
+        ipc.write_all(&state.pending)?;
+        wipe(&mut state.pending);
+        Ok(ipc)
     }
 
     /// Closes the connection to the Discord IPC server.
-    pub fn close(&mut self) -> Result<(), Error> {
-        self.write_ipc(2, "".to_string())?;
+    pub fn close(&self) -> Result<(), Error> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        self.lock().write_ipc(Opcode::Close, "".to_string())?;
         Ok(())
     }
+
+    /// Closes the connection and waits up to `timeout` for Discord to acknowledge it.
+    ///
+    /// Frames received before the acknowledgement are discarded. Returns once Discord
+    /// answers with its own close frame, ends the connection, or the timeout elapses.
+    /// Transports without read timeouts return as soon as the close frame is sent.
+    pub fn close_gracefully(&self, timeout: Duration) -> Result<(), Error> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let deadline = std::time::Instant::now() + timeout;
+        let mut state = self.lock();
+        state.write_ipc(Opcode::Close, "".to_string())?;
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let result = loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                break Ok(());
+            }
+            if state.ipc()?.set_read_timeout(Some(remaining)).is_err() {
+                break Ok(());
+            }
+
+            match state.read_frame(self.builder.max_frame_size) {
+                Ok(frame) if frame.opcode == Opcode::Close => break Ok(()),
+                Ok(_) => continue,
+                Err(Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock
+                            | std::io::ErrorKind::TimedOut
+                            | std::io::ErrorKind::UnexpectedEof
+                    ) =>
+                {
+                    break Ok(());
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        let _ = state.ipc()?.set_read_timeout(None);
+        result
+    }
+
+    /// Sends a ping to Discord and returns the time until the matching pong arrived.
+    ///
+    /// Messages that arrive in the meantime are kept for `poll_events`. Returns a
+    /// `TimedOut` I/O error if no pong arrives within `timeout`, and
+    /// `Error::ClosedByServer` if Discord closes the connection first. When the client
+    /// is disabled or in dry-run mode, nothing is sent and `Duration::ZERO` is returned.
+    ///
+    /// Transports without read timeouts, such as named pipes on Windows, could wait for
+    /// the pong forever, so they return an `Unsupported` I/O error before anything is
+    /// sent.
+    pub fn ping(&self, timeout: Duration) -> Result<Duration, Error> {
+        if !self.is_enabled() || self.dry_run {
+            return Ok(Duration::ZERO);
+        }
+        let payload = json!({ "nonce": Uuid::new_v4().to_string() }).to_string();
+        let mut state = self.lock();
+        // Fails on transports without read timeouts.
+        state.ipc()?.set_read_timeout(None)?;
+        let start = std::time::Instant::now();
+        state.write_ipc(Opcode::Ping, payload.clone())?;
+
+        let result = state.await_pong(
+            payload.as_bytes(),
+            start + timeout,
+            self.builder.max_frame_size,
+        );
+        let _ = state.ipc()?.set_read_timeout(None);
+        result.map(|()| start.elapsed())
+    }
+}
+
+impl State {
+    /// Records an activity as applied, keeping at most `history_len` entries of history.
+    fn record(&mut self, activity: Activity, history_len: usize) {
+        if history_len == 0 {
+            self.history.clear();
+        } else {
+            while self.history.len() >= history_len {
+                self.history.pop_front();
+            }
+            self.history.push_back(HistoryEntry {
+                activity: activity.clone(),
+                applied_at: SystemTime::now(),
+            });
+        }
+        self.last_activity = Some(activity);
+    }
+
+    /// Returns the connection to Discord.
+    fn ipc(&mut self) -> Result<&mut dyn Ipc, Error> {
+        match &mut self.ipc {
+            Some(ipc) => Ok(ipc.as_mut()),
+            None => Err(Error::ConnectionNotFound),
+        }
+    }
+
+    /// Writes a frame without blocking, keeping any unsent remainder in `pending`.
+    fn try_write(&mut self, mut bytes: Vec<u8>) -> Result<(), Error> {
+        let mut pending = std::mem::take(&mut self.pending);
+        let result = self.try_write_buffers(&pending, &bytes);
+        wipe(&mut pending);
+        wipe(&mut bytes);
+        result
+    }
+
+    /// Writes `pending` and then `bytes` without blocking, keeping any unsent remainder.
+    fn try_write_buffers(&mut self, pending: &[u8], bytes: &[u8]) -> Result<(), Error> {
+        let written = write_nonblocking(self.ipc()?, pending)?;
+        if written < pending.len() {
+            self.pending = pending[written..].to_vec();
+            return Err(Error::WouldBlock);
+        }
+
+        let written = write_nonblocking(self.ipc()?, bytes)?;
+        if written == 0 {
+            return Err(Error::WouldBlock);
+        }
+        self.pending = bytes[written..].to_vec();
+        Ok(())
+    }
+
+    /// Appends every byte that can be read without blocking to `received`.
+    ///
+    /// Returns whether Discord ended the connection.
+    fn read_available(&mut self) -> Result<bool, Error> {
+        let mut buf = [0u8; 4096];
+        loop {
+            match self.ipc()?.read(&mut buf) {
+                Ok(0) => return Ok(true),
+                Ok(n) => self.received.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Returns `events` and keeps `error` for the next `poll_events` call, or returns
+    /// `error` right away if there are no events.
+    fn defer(
+        &mut self,
+        events: Vec<serde_json::Value>,
+        error: Error,
+    ) -> Result<Vec<serde_json::Value>, Error> {
+        if events.is_empty() {
+            return Err(error);
+        }
+        self.deferred = Some(error);
+        Ok(events)
+    }
+
+    /// Waits until a pong carrying `payload` arrives, leaving other frames in `received`.
+    fn await_pong(
+        &mut self,
+        payload: &[u8],
+        deadline: std::time::Instant,
+        max_frame_size: u32,
+    ) -> Result<(), Error> {
+        let mut offset = 0;
+        let mut buf = [0u8; 4096];
+        loop {
+            loop {
+                let (frame, len) = match Frame::decode(&self.received[offset..], max_frame_size) {
+                    Ok(Some(decoded)) => decoded,
+                    Ok(None) => break,
+                    // Unknown frames are left for `poll_events` to report.
+                    Err(Error::InvalidOpcode(_)) => {
+                        offset += encoded_len(&self.received[offset..]);
+                        continue;
+                    }
+                    Err(error) => return Err(error),
+                };
+                if frame.opcode == Opcode::Pong && frame.payload == payload {
+                    #[cfg(feature = "debug-frames")]
+                    crate::debug_frames::log_frame(
+                        crate::debug_frames::Direction::Received,
+                        &frame,
+                    );
+                    self.received.drain(offset..offset + len);
+                    return Ok(());
+                }
+                if let Some(error) = frame.close_error() {
+                    return Err(error);
+                }
+                offset += len;
+            }
+
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                return Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into());
+            }
+            self.ipc()?.set_read_timeout(Some(remaining))?;
+            match self.ipc()?.read(&mut buf) {
+                Ok(0) => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => self.received.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Reads a frame from the Discord IPC server.
+    fn read_frame(&mut self, max_frame_size: u32) -> Result<Frame, Error> {
+        let frame = Frame::read_from(self.ipc()?, max_frame_size)?;
+        #[cfg(feature = "debug-frames")]
+        crate::debug_frames::log_frame(crate::debug_frames::Direction::Received, &frame);
+        Ok(frame)
+    }
+
+    /// Writes a message to the Discord IPC server.
+    fn write_ipc(&mut self, opcode: Opcode, payload: String) -> Result<(), Error> {
+        let mut frame = Frame::new(opcode, payload.into_bytes());
+        if let Err(e) = frame.check_send_size() {
+            wipe(&mut frame.payload);
+            return Err(e);
+        }
+        if !self.pending.is_empty() {
+            let mut pending = std::mem::take(&mut self.pending);
+            let result = self.ipc()?.write_all(&pending);
+            wipe(&mut pending);
+            result?;
+        }
+        #[cfg(feature = "debug-frames")]
+        crate::debug_frames::log_frame(crate::debug_frames::Direction::Sent, &frame);
+        let result = match &mut self.ipc {
+            Some(ipc) => frame.write_to(ipc.as_mut()),
+            // In dry-run mode, the frame is built but not sent.
+            None if self.dry_run => frame.encode().map(|mut buf| wipe(&mut buf)),
+            None => Err(Error::ConnectionNotFound),
+        };
+        wipe(&mut frame.payload);
+        result
+    }
+}
+
+/// Treats an `Unsupported` I/O error as success.
+fn ignore_unsupported(error: std::io::Error) -> std::io::Result<()> {
+    match error.kind() {
+        std::io::ErrorKind::Unsupported => Ok(()),
+        _ => Err(error),
+    }
+}
+
+/// Returns whether clients are disabled through `DISABLED_ENV_VAR`.
+fn disabled_by_env() -> bool {
+    std::env::var(DISABLED_ENV_VAR)
+        .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
+
+/// Writes as much of `buf` as possible to a non-blocking writer.
+fn write_nonblocking<W: Write + ?Sized>(writer: &mut W, buf: &[u8]) -> Result<usize, Error> {
+    let mut written = 0;
+    while written < buf.len() {
+        match writer.write(&buf[written..]) {
+            Ok(0) => return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into()),
+            Ok(n) => written += n,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    /// An in-memory connection that reads pre-baked bytes and accepts a limited number
+    /// of written bytes before it would block.
+    struct MockIpc {
+        input: std::io::Cursor<Vec<u8>>,
+        written: Arc<Mutex<Vec<u8>>>,
+        budget: usize,
+    }
+
+    impl Read for MockIpc {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.input.read(buf)? {
+                0 => Err(std::io::ErrorKind::WouldBlock.into()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl Write for MockIpc {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.budget == 0 {
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            }
+            let n = buf.len().min(self.budget);
+            self.budget -= n;
+            self.written.lock().unwrap().extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Ipc for MockIpc {
+        fn connect_with(_discovery: &Discovery) -> Result<Self, Error> {
+            Err(Error::ConnectionNotFound)
+        }
+
+        fn set_nonblocking(&self, _nonblocking: bool) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn set_read_timeout(&self, _timeout: Option<Duration>) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Returns a state connected to a mock reading `input`, and the bytes it writes.
+    fn state(input: Vec<u8>, budget: usize) -> (State, Arc<Mutex<Vec<u8>>>) {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let ipc = MockIpc {
+            input: std::io::Cursor::new(input),
+            written: Arc::clone(&written),
+            budget,
+        };
+        let state = State {
+            ipc: Some(Box::new(ipc)),
+            dry_run: false,
+            pending: Vec::new(),
+            last_activity: None,
+            history: VecDeque::new(),
+            stack: Vec::new(),
+            session_start: None,
+            ready: Ready::default(),
+            received: Vec::new(),
+            deferred: None,
+        };
+        (state, written)
+    }
+
+    /// Gives the mock connection of `state` room for `budget` more bytes.
+    fn refill(state: &mut State, written: &Arc<Mutex<Vec<u8>>>, budget: usize) {
+        let input = std::io::Cursor::new(Vec::new());
+        let written = Arc::clone(written);
+        state.ipc = Some(Box::new(MockIpc {
+            input,
+            written,
+            budget,
+        }));
+    }
+
+    /// Returns a client that uses `state` as if it had just connected.
+    fn client(state: State) -> Client {
+        let builder = ClientBuilder::new(1234567890123456789u64);
+        Client {
+            client_id: builder.client_id.clone(),
+            auto_elapsed: builder.auto_elapsed,
+            enabled: AtomicBool::new(true),
+            dry_run: false,
+            builder,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Encodes a frame.
+    fn frame(opcode: Opcode, payload: &[u8]) -> Vec<u8> {
+        Frame::new(opcode, payload.to_vec()).encode().unwrap()
+    }
+
+    #[test]
+    fn try_write_keeps_the_unsent_remainder() {
+        let (mut state, written) = state(Vec::new(), 3);
+        state.try_write(b"abcdef".to_vec()).unwrap();
+        assert_eq!(state.pending, b"def");
+        assert_eq!(*written.lock().unwrap(), b"abc");
+
+        // The remainder goes out before the next frame, which only starts once it has.
+        refill(&mut state, &written, 1);
+        assert!(matches!(
+            state.try_write(b"ghi".to_vec()),
+            Err(Error::WouldBlock)
+        ));
+        assert_eq!(state.pending, b"ef");
+
+        refill(&mut state, &written, 4);
+        state.try_write(b"ghi".to_vec()).unwrap();
+        assert_eq!(state.pending, b"i");
+        assert_eq!(*written.lock().unwrap(), b"abcdefgh");
+    }
+
+    #[test]
+    fn try_write_would_block_without_room() {
+        let (mut state, written) = state(Vec::new(), 0);
+        assert!(matches!(
+            state.try_write(b"abc".to_vec()),
+            Err(Error::WouldBlock)
+        ));
+        assert!(state.pending.is_empty());
+        assert!(written.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn await_pong_skips_other_frames() {
+        let event = frame(Opcode::Frame, br#"{"evt":"READY"}"#);
+        let mut input = frame(Opcode::Pong, b"other");
+        input.extend(&event);
+        input.extend(frame(Opcode::Pong, b"mine"));
+        let (mut state, _) = state(input, 0);
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        state
+            .await_pong(b"mine", deadline, DEFAULT_MAX_FRAME_SIZE)
+            .unwrap();
+
+        let mut left = frame(Opcode::Pong, b"other");
+        left.extend(&event);
+        assert_eq!(state.received, left);
+    }
+
+    #[test]
+    fn await_pong_times_out_without_a_matching_pong() {
+        let (mut state, _) = state(frame(Opcode::Pong, b"other"), 0);
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert!(matches!(
+            state.await_pong(b"mine", deadline, DEFAULT_MAX_FRAME_SIZE),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::TimedOut
+        ));
+        assert_eq!(state.received, frame(Opcode::Pong, b"other"));
+    }
+
+    #[test]
+    fn await_pong_reports_a_close() {
+        let close = frame(Opcode::Close, br#"{"code":1000,"message":"bye"}"#);
+        let (mut state, _) = state(close, 0);
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert!(matches!(
+            state.await_pong(b"mine", deadline, DEFAULT_MAX_FRAME_SIZE),
+            Err(Error::ClosedByServer { code: 1000, .. })
+        ));
+    }
+
+    #[test]
+    fn commands_fail_after_the_connection_is_dropped() {
+        // A length over the maximum leaves the stream unframed.
+        let mut input = 1u32.to_le_bytes().to_vec();
+        input.extend_from_slice(&u32::MAX.to_le_bytes());
+        let (state, written) = state(input, usize::MAX);
+        let client = client(state);
+
+        assert!(matches!(
+            client.poll_events(),
+            Err(Error::FrameTooLarge { .. })
+        ));
+        assert!(matches!(
+            client.set_activity(Activity::new().set_details("Playing".to_string())),
+            Err(Error::ConnectionNotFound)
+        ));
+        assert!(matches!(client.close(), Err(Error::ConnectionNotFound)));
+        assert!(written.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(not(any(unix, windows)))]
+    fn connecting_is_unsupported_without_a_transport() {
+        assert!(matches!(
+            Client::new(1234567890123456789u64),
+            Err(Error::UnsupportedPlatform)
+        ));
+    }
 }
@@ -1,3 +1,5 @@
+use crate::discord_connection::ClientId;
+use crate::frame::Frame;
 use thiserror::Error;
 
 /// Represents the possible errors that can occur.
@@ -12,7 +14,127 @@ pub enum Error {
     /// The IPC connection was not found.
     #[error("IPC Connection Not Found")]
     ConnectionNotFound,
+    /// Discord IPC is not supported on this platform.
+    #[error("Unsupported Platform")]
+    UnsupportedPlatform,
     /// The IPC handshake failed.
     #[error("IPC Handshake Failed")]
     HandshakeFailed,
+    /// The client ID is not a numeric snowflake.
+    #[error("Invalid Client ID: expected a numeric application ID")]
+    InvalidClientId,
+    /// An activity has more buttons than Discord allows.
+    #[error("Too Many Buttons: {count} (max {max})")]
+    TooManyButtons {
+        /// The number of buttons on the activity.
+        count: usize,
+        /// The maximum number of buttons allowed.
+        max: usize,
+    },
+    /// A button has no label.
+    #[error("Button Label Missing")]
+    ButtonLabelMissing,
+    /// A button label is longer than Discord allows.
+    #[error("Button Label Too Long: {len} characters (max {max})")]
+    ButtonLabelTooLong {
+        /// The length of the label, in characters.
+        len: usize,
+        /// The maximum length allowed, in characters.
+        max: usize,
+    },
+    /// A button has no URL.
+    #[error("Button URL Missing")]
+    ButtonUrlMissing,
+    /// A button URL does not use the `https` or `discord` scheme.
+    #[error("Invalid Button URL: {0}")]
+    InvalidButtonUrl(String),
+    /// An activity has buttons together with a join or spectate secret.
+    #[error("Buttons Cannot Be Combined With Join Or Spectate Secrets")]
+    ButtonsWithSecrets,
+    /// An activity has secrets but no party ID.
+    #[error("Secrets Require A Party ID")]
+    SecretsWithoutParty,
+    /// A certified device ID is not a UUID or audio endpoint ID.
+    #[error("Invalid Device ID: {0}")]
+    InvalidDeviceId(String),
+    /// A certified device has no vendor name.
+    #[error("Device Vendor Missing")]
+    DeviceVendorMissing,
+    /// A certified device has no model name.
+    #[error("Device Model Missing")]
+    DeviceModelMissing,
+    /// A setting lies outside the range Discord accepts.
+    #[error("Value Out Of Range: {field} is {value} (must be {min} to {max})")]
+    ValueOutOfRange {
+        /// The name of the setting.
+        field: &'static str,
+        /// The value of the setting.
+        value: f64,
+        /// The lowest value allowed.
+        min: f64,
+        /// The highest value allowed.
+        max: f64,
+    },
+    /// A D-Bus error occurred.
+    #[cfg(feature = "dbus")]
+    #[error("D-Bus Error: {0}")]
+    DBus(#[from] zbus::Error),
+    /// A console control handler could not be installed.
+    #[cfg(all(feature = "signals", windows))]
+    #[error("Signal Handler Error: {0}")]
+    Signal(#[from] ctrlc::Error),
+    /// A TOML error occurred.
+    #[cfg(feature = "toml")]
+    #[error("TOML Error: {0}")]
+    Toml(#[from] toml::de::Error),
+    /// A file has a format that is not supported by the enabled features.
+    #[error("Unsupported File Format: {}", .0.display())]
+    UnsupportedFileFormat(std::path::PathBuf),
+    /// No client is connected for the given client ID.
+    #[error("Unknown Client: {0}")]
+    UnknownClient(ClientId),
+    /// The operation would block.
+    #[error("Operation Would Block")]
+    WouldBlock,
+    /// A frame exceeded the maximum allowed size.
+    #[error("Frame Too Large: {size} bytes (max {max})")]
+    FrameTooLarge {
+        /// The size of the frame payload, in bytes.
+        size: u64,
+        /// The maximum allowed size, in bytes.
+        max: u64,
+    },
+    /// A frame to be sent is larger than Discord accepts.
+    #[error("Payload Too Large: {actual} bytes (max {limit})")]
+    PayloadTooLarge {
+        /// The maximum frame size Discord accepts, in bytes.
+        limit: usize,
+        /// The size of the encoded frame, in bytes.
+        actual: usize,
+    },
+    /// A raw activity does not match the SET_ACTIVITY schema.
+    #[error(
+        "Invalid Activity JSON: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    InvalidActivityJson(Vec<crate::schema::Violation>),
+    /// A frame had an unknown opcode.
+    #[error("Invalid Opcode: {0}")]
+    InvalidOpcode(u32),
+    /// A frame payload was not valid UTF-8.
+    #[error("Invalid UTF-8 in {:?} Frame: {source}", frame.opcode)]
+    InvalidUtf8 {
+        /// The frame that failed to decode.
+        frame: Frame,
+        /// The underlying UTF-8 error.
+        source: std::str::Utf8Error,
+    },
+    /// Discord closed the connection.
+    #[error("Connection closed by Discord: {message} (code {code})")]
+    ClosedByServer {
+        /// The close code sent by Discord.
+        code: i64,
+        /// The reason sent by Discord.
+        message: String,
+    },
 }
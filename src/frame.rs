@@ -0,0 +1,367 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// The default maximum size of a frame payload, in bytes.
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 64 * 1024;
+
+/// The largest frame Discord accepts, header included, in bytes.
+pub const MAX_SEND_FRAME_SIZE: usize = 64 * 1024;
+
+/// The size of a frame header, in bytes.
+const HEADER_SIZE: usize = 8;
+
+/// Represents the opcode of an IPC frame.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    /// The initial handshake.
+    Handshake = 0,
+    /// A regular command or event payload.
+    Frame = 1,
+    /// The connection is being closed.
+    Close = 2,
+    /// A ping request.
+    Ping = 3,
+    /// A ping response.
+    Pong = 4,
+}
+
+impl TryFrom<u32> for Opcode {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Error> {
+        match value {
+            0 => Ok(Opcode::Handshake),
+            1 => Ok(Opcode::Frame),
+            2 => Ok(Opcode::Close),
+            3 => Ok(Opcode::Ping),
+            4 => Ok(Opcode::Pong),
+            _ => Err(Error::InvalidOpcode(value)),
+        }
+    }
+}
+
+/// Represents how invalid UTF-8 in a frame payload is handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Utf8Policy {
+    /// Replace invalid sequences with U+FFFD.
+    #[default]
+    Lossy,
+    /// Reject the frame with `Error::InvalidUtf8`.
+    Strict,
+}
+
+/// Represents a single frame of the Discord IPC protocol.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// The opcode of the frame.
+    pub opcode: Opcode,
+    /// The raw payload of the frame.
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Creates a new `Frame`.
+    pub fn new(opcode: Opcode, payload: Vec<u8>) -> Self {
+        Frame { opcode, payload }
+    }
+
+    /// Reads a frame from `reader`, rejecting payloads larger than `max_frame_size`.
+    ///
+    /// The length header is validated before any payload memory is allocated, so this
+    /// is safe to call on untrusted input. A frame with an unknown opcode is reported
+    /// only after its payload was read, so the next read starts at the following frame.
+    pub fn read_from<R: Read + ?Sized>(reader: &mut R, max_frame_size: u32) -> Result<Self, Error> {
+        let mut header = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+
+        let opcode = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+        if len > max_frame_size {
+            return Err(Error::FrameTooLarge {
+                size: u64::from(len),
+                max: u64::from(max_frame_size),
+            });
+        }
+
+        let len = usize::try_from(len).map_err(|_| Error::FrameTooLarge {
+            size: u64::from(len),
+            max: usize::MAX as u64,
+        })?;
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload)?;
+
+        let opcode = Opcode::try_from(opcode)?;
+        Ok(Frame { opcode, payload })
+    }
+
+    /// Decodes a frame from the start of `buf`.
+    ///
+    /// Returns the frame and the number of bytes it took up, or `None` if `buf` does not
+    /// hold a complete frame yet. `Error::InvalidOpcode` is only returned once the whole
+    /// frame has arrived, so the frame can be skipped with `encoded_len`.
+    pub fn decode(buf: &[u8], max_frame_size: u32) -> Result<Option<(Self, usize)>, Error> {
+        if buf.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+        let len = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        let total = encoded_len(buf);
+
+        // An oversized length is reported from the header alone.
+        if len <= max_frame_size && buf.len() < total {
+            return Ok(None);
+        }
+        let frame = Self::read_from(&mut &buf[..buf.len().min(total)], max_frame_size)?;
+        Ok(Some((frame, total)))
+    }
+
+    /// Converts the payload into a string according to `policy`.
+    pub fn into_string(self, policy: Utf8Policy) -> Result<String, Error> {
+        match policy {
+            Utf8Policy::Lossy => Ok(String::from_utf8_lossy(&self.payload).into_owned()),
+            Utf8Policy::Strict => match std::str::from_utf8(&self.payload) {
+                Ok(payload) => Ok(payload.to_string()),
+                Err(source) => Err(Error::InvalidUtf8 {
+                    frame: self,
+                    source,
+                }),
+            },
+        }
+    }
+
+    /// Converts a close frame into `Error::ClosedByServer`.
+    ///
+    /// Returns `None` if the frame is not a close frame. Fields missing from the payload
+    /// are reported as code `0` and an empty message.
+    pub fn close_error(&self) -> Option<Error> {
+        #[derive(Default, Deserialize)]
+        #[serde(default)]
+        struct CloseReason {
+            code: i64,
+            message: String,
+        }
+
+        if self.opcode != Opcode::Close {
+            return None;
+        }
+        let reason: CloseReason = serde_json::from_slice(&self.payload).unwrap_or_default();
+        Some(Error::ClosedByServer {
+            code: reason.code,
+            message: reason.message,
+        })
+    }
+
+    /// Encodes the frame into its wire format.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let len = u32::try_from(self.payload.len()).map_err(|_| Error::FrameTooLarge {
+            size: self.payload.len() as u64,
+            max: u64::from(u32::MAX),
+        })?;
+
+        let mut buf = Vec::with_capacity(HEADER_SIZE + self.payload.len());
+        buf.extend_from_slice(&(self.opcode as u32).to_le_bytes());
+        buf.extend_from_slice(&len.to_le_bytes());
+        buf.extend_from_slice(&self.payload);
+        Ok(buf)
+    }
+
+    /// Checks that the encoded frame fits within `MAX_SEND_FRAME_SIZE`.
+    ///
+    /// Discord drops the connection when it receives a larger frame, so this is checked
+    /// before anything is written.
+    pub fn check_send_size(&self) -> Result<(), Error> {
+        let actual = HEADER_SIZE.saturating_add(self.payload.len());
+        if actual > MAX_SEND_FRAME_SIZE {
+            return Err(Error::PayloadTooLarge {
+                limit: MAX_SEND_FRAME_SIZE,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Writes the frame to `writer`.
+    pub fn write_to<W: Write + ?Sized>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut buf = self.encode()?;
+        let result = writer.write_all(&buf);
+        wipe(&mut buf);
+        Ok(result?)
+    }
+}
+
+/// Returns the size of the frame at the start of `buf`, header included.
+///
+/// `buf` must hold at least a frame header.
+pub(crate) fn encoded_len(buf: &[u8]) -> usize {
+    let len = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+    usize::try_from(len)
+        .unwrap_or(usize::MAX)
+        .saturating_add(HEADER_SIZE)
+}
+
+/// Wipes a buffer that may hold secrets if the `zeroize` feature is enabled.
+pub(crate) fn wipe(buf: &mut Vec<u8>) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(buf);
+    #[cfg(not(feature = "zeroize"))]
+    let _ = buf;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes a frame header with a raw opcode and length.
+    fn header(opcode: u32, len: u32) -> Vec<u8> {
+        let mut buf = opcode.to_le_bytes().to_vec();
+        buf.extend_from_slice(&len.to_le_bytes());
+        buf
+    }
+
+    #[test]
+    fn decode_round_trips_encode() {
+        let frame = Frame::new(Opcode::Frame, b"{\"cmd\":\"DISPATCH\"}".to_vec());
+        let buf = frame.encode().unwrap();
+
+        let (decoded, len) = Frame::decode(&buf, DEFAULT_MAX_FRAME_SIZE)
+            .unwrap()
+            .unwrap();
+        assert_eq!(len, buf.len());
+        assert_eq!(decoded.opcode, Opcode::Frame);
+        assert_eq!(decoded.payload, frame.payload);
+    }
+
+    #[test]
+    fn decode_waits_for_a_complete_frame() {
+        let buf = Frame::new(Opcode::Ping, b"hello".to_vec())
+            .encode()
+            .unwrap();
+        for end in 0..buf.len() {
+            assert!(
+                Frame::decode(&buf[..end], DEFAULT_MAX_FRAME_SIZE)
+                    .unwrap()
+                    .is_none()
+            );
+        }
+    }
+
+    #[test]
+    fn decode_leaves_the_next_frame_in_the_buffer() {
+        let mut buf = Frame::new(Opcode::Ping, b"one".to_vec()).encode().unwrap();
+        let first_len = buf.len();
+        buf.extend(Frame::new(Opcode::Pong, b"two".to_vec()).encode().unwrap());
+
+        let (first, len) = Frame::decode(&buf, DEFAULT_MAX_FRAME_SIZE)
+            .unwrap()
+            .unwrap();
+        assert_eq!((first.opcode, len), (Opcode::Ping, first_len));
+        let (second, _) = Frame::decode(&buf[len..], DEFAULT_MAX_FRAME_SIZE)
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.opcode, Opcode::Pong);
+        assert_eq!(second.payload, b"two");
+    }
+
+    #[test]
+    fn decode_rejects_an_oversized_length_from_the_header_alone() {
+        let buf = header(1, u32::MAX);
+        assert!(matches!(
+            Frame::decode(&buf, DEFAULT_MAX_FRAME_SIZE),
+            Err(Error::FrameTooLarge { size, max })
+                if size == u64::from(u32::MAX) && max == u64::from(DEFAULT_MAX_FRAME_SIZE)
+        ));
+    }
+
+    #[test]
+    fn decode_reports_an_unknown_opcode_once_the_frame_is_complete() {
+        let mut buf = header(9, 4);
+        buf.extend_from_slice(b"ab");
+        assert!(
+            Frame::decode(&buf, DEFAULT_MAX_FRAME_SIZE)
+                .unwrap()
+                .is_none()
+        );
+
+        buf.extend_from_slice(b"cd");
+        assert!(matches!(
+            Frame::decode(&buf, DEFAULT_MAX_FRAME_SIZE),
+            Err(Error::InvalidOpcode(9))
+        ));
+        assert_eq!(encoded_len(&buf), buf.len());
+    }
+
+    #[test]
+    fn read_from_stays_framed_after_an_unknown_opcode() {
+        let mut buf = header(9, 3);
+        buf.extend_from_slice(b"abc");
+        buf.extend(Frame::new(Opcode::Pong, b"next".to_vec()).encode().unwrap());
+        let mut reader = buf.as_slice();
+
+        assert!(matches!(
+            Frame::read_from(&mut reader, DEFAULT_MAX_FRAME_SIZE),
+            Err(Error::InvalidOpcode(9))
+        ));
+        let frame = Frame::read_from(&mut reader, DEFAULT_MAX_FRAME_SIZE).unwrap();
+        assert_eq!(frame.opcode, Opcode::Pong);
+        assert_eq!(frame.payload, b"next");
+    }
+
+    #[test]
+    fn read_from_fails_on_a_truncated_payload() {
+        let mut buf = header(1, 10);
+        buf.extend_from_slice(b"short");
+        assert!(matches!(
+            Frame::read_from(&mut buf.as_slice(), DEFAULT_MAX_FRAME_SIZE),
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn into_string_follows_the_utf8_policy() {
+        let frame = Frame::new(Opcode::Frame, vec![b'a', 0xff, b'b']);
+        assert_eq!(
+            frame.clone().into_string(Utf8Policy::Lossy).unwrap(),
+            "a\u{fffd}b"
+        );
+        assert!(matches!(
+            frame.into_string(Utf8Policy::Strict),
+            Err(Error::InvalidUtf8 { .. })
+        ));
+    }
+
+    #[test]
+    fn close_error_reads_the_code_and_message() {
+        let frame = Frame::new(
+            Opcode::Close,
+            br#"{"code":4000,"message":"Invalid Client ID"}"#.to_vec(),
+        );
+        assert!(matches!(
+            frame.close_error(),
+            Some(Error::ClosedByServer { code: 4000, message }) if message == "Invalid Client ID"
+        ));
+        assert!(
+            Frame::new(Opcode::Frame, Vec::new())
+                .close_error()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn check_send_size_counts_the_header() {
+        let fits = Frame::new(Opcode::Frame, vec![0; MAX_SEND_FRAME_SIZE - HEADER_SIZE]);
+        assert!(fits.check_send_size().is_ok());
+
+        let too_large = Frame::new(
+            Opcode::Frame,
+            vec![0; MAX_SEND_FRAME_SIZE - HEADER_SIZE + 1],
+        );
+        assert!(matches!(
+            too_large.check_send_size(),
+            Err(Error::PayloadTooLarge { limit: MAX_SEND_FRAME_SIZE, actual })
+                if actual == MAX_SEND_FRAME_SIZE + 1
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::activities::{Activity, redact_secrets};
+use crate::discord_connection::Client;
+use crate::error::Error;
+use crate::frame::DEFAULT_MAX_FRAME_SIZE;
+use serde_json::json;
+use std::io::{ErrorKind, Read};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// A localhost-only HTTP server for controlling the presence.
+///
+/// Endpoints:
+/// - `POST /activity` sets the activity from a JSON body.
+/// - `DELETE /activity` clears the activity.
+/// - `GET /status` returns the client ID and current activity, with secrets redacted.
+///
+/// Every request must carry `Authorization: Bearer <token>` with the token passed to
+/// `bind`, and a `Host` of `127.0.0.1:<port>` or `localhost:<port>`, which blocks DNS
+/// rebinding. Requests with an `Origin` header are rejected unless the origin was
+/// allowed with `allow_origin`, so web pages cannot drive the presence, and
+/// `POST /activity` requires `Content-Type: application/json` and a body of at most
+/// `DEFAULT_MAX_FRAME_SIZE` bytes.
+pub struct HttpServer {
+    server: Server,
+    client: Arc<Client>,
+    token: String,
+    allowed_origins: Vec<String>,
+}
+
+impl HttpServer {
+    /// Binds the server to `127.0.0.1` on the given port.
+    ///
+    /// Pass `0` to let the operating system pick a free port. Requests must present
+    /// `token` as a bearer token, which must not be empty.
+    pub fn bind(client: Arc<Client>, port: u16, token: String) -> Result<Self, Error> {
+        if token.is_empty() {
+            return Err(std::io::Error::new(ErrorKind::InvalidInput, "empty token").into());
+        }
+        let server = Server::http(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+            .map_err(std::io::Error::other)?;
+        Ok(HttpServer {
+            server,
+            client,
+            token,
+            allowed_origins: Vec::new(),
+        })
+    }
+
+    /// Allows requests carrying the given `Origin` header, such as
+    /// `http://localhost:3000`.
+    pub fn allow_origin(mut self, origin: String) -> Self {
+        self.allowed_origins.push(origin);
+        self
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Handles requests until `stop` is called.
+    pub fn run(&self) {
+        for request in self.server.incoming_requests() {
+            self.handle(request);
+        }
+    }
+
+    /// Stops a running `run` loop.
+    pub fn stop(&self) {
+        self.server.unblock();
+    }
+
+    /// Handles a single request.
+    fn handle(&self, mut request: Request) {
+        let (status, body) = match self.check(&request) {
+            Err(rejection) => rejection,
+            Ok(()) => match (request.method(), request.url()) {
+                (Method::Post, "/activity") => self.set_activity(&mut request),
+                (Method::Delete, "/activity") => self.clear_activity(),
+                (Method::Get, "/status") => self.status(),
+                (_, "/activity") | (_, "/status") => (405, error_body("Method Not Allowed")),
+                _ => (404, error_body("Not Found")),
+            },
+        };
+
+        let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+            .expect("static header is valid");
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(header);
+        let _ = request.respond(response);
+    }
+
+    /// Checks the `Host`, `Origin` and `Authorization` headers of a request.
+    fn check(&self, request: &Request) -> Result<(), (u16, String)> {
+        let port = self.local_addr().map_or(0, |addr| addr.port());
+        let host_allowed = header(request, "Host").is_some_and(|host| {
+            host == format!("127.0.0.1:{}", port) || host == format!("localhost:{}", port)
+        });
+        if !host_allowed {
+            return Err((403, error_body("Host Not Allowed")));
+        }
+
+        if let Some(origin) = header(request, "Origin")
+            && !self.allowed_origins.iter().any(|allowed| allowed == origin)
+        {
+            return Err((403, error_body("Origin Not Allowed")));
+        }
+
+        let token =
+            header(request, "Authorization").and_then(|value| value.strip_prefix("Bearer "));
+        if !token.is_some_and(|token| constant_time_eq(token.as_bytes(), self.token.as_bytes())) {
+            return Err((401, error_body("Unauthorized")));
+        }
+        Ok(())
+    }
+
+    /// Handles `POST /activity`.
+    fn set_activity(&self, request: &mut Request) -> (u16, String) {
+        let is_json = header(request, "Content-Type").is_some_and(|value| {
+            let media_type = value.split(';').next().unwrap_or_default();
+            media_type.trim().eq_ignore_ascii_case("application/json")
+        });
+        if !is_json {
+            return (415, error_body("Unsupported Media Type"));
+        }
+
+        let limit = DEFAULT_MAX_FRAME_SIZE as usize;
+        if request.body_length().is_some_and(|length| length > limit) {
+            return (413, error_body("Payload Too Large"));
+        }
+        // Read one byte past the limit, so a body without a `Content-Length` that is too
+        // large is rejected instead of cut off.
+        let mut body = Vec::new();
+        let mut reader = request.as_reader().take(limit as u64 + 1);
+        if let Err(e) = reader.read_to_end(&mut body) {
+            return (400, error_body(&e.to_string()));
+        }
+        if body.len() > limit {
+            return (413, error_body("Payload Too Large"));
+        }
+        let body = match String::from_utf8(body) {
+            Ok(body) => body,
+            Err(e) => return (400, error_body(&e.to_string())),
+        };
+
+        let activity = match Activity::from_json_str(&body) {
+            Ok(activity) => activity,
+            Err(e) => return (400, error_body(&e.to_string())),
+        };
+        if let Err(e) = activity.validate() {
+            return (422, error_body(&e.to_string()));
+        }
+
+        match self.client.set_activity(activity) {
+            Ok(()) => (204, String::new()),
+            Err(e) => (502, error_body(&e.to_string())),
+        }
+    }
+
+    /// Handles `DELETE /activity`.
+    fn clear_activity(&self) -> (u16, String) {
+        match self.client.clear_activity() {
+            Ok(()) => (204, String::new()),
+            Err(e) => (502, error_body(&e.to_string())),
+        }
+    }
+
+    /// Handles `GET /status`.
+    fn status(&self) -> (u16, String) {
+        let mut activity = json!(self.client.last_activity());
+        redact_secrets(&mut activity);
+        let body = json!({
+            "client_id": self.client.client_id.as_str(),
+            "activity": activity,
+        })
+        .to_string();
+        (200, body)
+    }
+}
+
+/// Returns the value of the first header of a request with the given name.
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+/// Compares two byte strings in time that depends only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Builds a JSON error body.
+fn error_body(message: &str) -> String {
+    json!({ "error": message }).to_string()
+}
@@ -2,7 +2,45 @@
 
 /// Module for handling Discord activities.
 pub mod activities;
+/// Module for serializable client settings.
+pub mod config;
+/// Module for sharing one connection between several local processes.
+#[cfg(feature = "daemon")]
+pub mod daemon;
+/// Module for exposing the client as a D-Bus service.
+#[cfg(feature = "dbus")]
+pub mod dbus;
+/// Module for logging IPC frames with secrets redacted.
+#[cfg(feature = "debug-frames")]
+pub mod debug_frames;
+/// Module for describing certified hardware devices.
+pub mod devices;
 /// Module for handling the Discord IPC connection.
 pub mod discord_connection;
 /// Module for handling errors.
 pub mod error;
+/// Module for encoding and decoding IPC frames.
+pub mod frame;
+/// Module for controlling the presence over a local HTTP server.
+#[cfg(feature = "http-server")]
+pub mod http_server;
+/// Module for the objects returned by Discord in responses and events.
+pub mod models;
+/// Module for managing connections for several applications.
+pub mod multi_client;
+/// Module for the platform-specific IPC transports.
+pub mod platform;
+/// Module for high-level activity presets.
+pub mod presets;
+/// Module for checking raw activities against the SET_ACTIVITY schema.
+pub mod schema;
+/// Module for clearing the presence when the process is terminated.
+#[cfg(feature = "signals")]
+pub mod signals;
+/// Module for a fake Discord IPC server for end-to-end tests.
+#[cfg(all(feature = "test-server", any(unix, windows)))]
+pub mod test_server;
+/// Module for computing activity timestamps.
+pub mod timers;
+/// Module for applying activities from a watched file.
+pub mod watch;
@@ -0,0 +1,503 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+
+/// The lowest voice activity threshold Discord accepts, in decibels.
+pub const MIN_VOICE_THRESHOLD: f64 = -100.0;
+
+/// The highest voice activity threshold Discord accepts, in decibels.
+pub const MAX_VOICE_THRESHOLD: f64 = 0.0;
+
+/// The longest push-to-talk release delay Discord accepts, in milliseconds.
+pub const MAX_PTT_DELAY: f64 = 2000.0;
+
+/// The base URL of the Discord CDN.
+pub const CDN_URL: &str = "https://cdn.discordapp.com";
+
+/// Represents a Discord user.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct User {
+    /// The ID of the user.
+    pub id: String,
+    /// The unique username of the user.
+    pub username: String,
+    /// The legacy four-digit tag of the user, `"0"` for migrated accounts.
+    pub discriminator: String,
+    /// The display name of the user, if set.
+    pub global_name: Option<String>,
+    /// The hash of the avatar of the user, if set.
+    pub avatar: Option<String>,
+    /// Whether the user is a bot.
+    pub bot: bool,
+    /// The public flags of the user.
+    pub flags: Option<u64>,
+    /// The Nitro subscription type of the user.
+    pub premium_type: Option<u8>,
+}
+
+impl User {
+    /// Returns whether the avatar of the user is animated.
+    pub fn is_avatar_animated(&self) -> bool {
+        self.avatar
+            .as_deref()
+            .is_some_and(|avatar| avatar.starts_with("a_"))
+    }
+
+    /// Returns the CDN URL of the avatar of the user.
+    ///
+    /// `size` should be a power of two from 16 to 4096. Requesting `ImageFormat::Gif`
+    /// for an avatar that is not animated falls back to PNG. Users without an avatar get
+    /// the URL of their default avatar, which is only available as PNG.
+    pub fn avatar_url(&self, size: u16, format: ImageFormat) -> String {
+        let Some(avatar) = &self.avatar else {
+            return self.default_avatar_url();
+        };
+        let format = match format {
+            ImageFormat::Gif if !self.is_avatar_animated() => ImageFormat::Png,
+            format => format,
+        };
+        format!(
+            "{}/avatars/{}/{}.{}?size={}",
+            CDN_URL,
+            self.id,
+            avatar,
+            format.extension(),
+            size
+        )
+    }
+
+    /// Returns the CDN URL of the default avatar of the user.
+    pub fn default_avatar_url(&self) -> String {
+        // Migrated accounts have the discriminator "0" and pick by ID instead.
+        let index = match self.discriminator.parse::<u64>() {
+            Ok(discriminator) if discriminator != 0 => discriminator % 5,
+            _ => (self.id.parse::<u64>().unwrap_or(0) >> 22) % 6,
+        };
+        format!("{}/embed/avatars/{}.png", CDN_URL, index)
+    }
+}
+
+/// Represents the format of an image on the Discord CDN.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    /// A PNG image.
+    #[default]
+    Png,
+    /// A JPEG image.
+    Jpeg,
+    /// A WebP image.
+    WebP,
+    /// A GIF image, for animated images.
+    Gif,
+}
+
+impl ImageFormat {
+    /// Returns the file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::WebP => "webp",
+            ImageFormat::Gif => "gif",
+        }
+    }
+}
+
+/// Represents a guild, as returned by `GET_GUILD` and `GET_GUILDS`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Guild {
+    /// The ID of the guild.
+    pub id: String,
+    /// The name of the guild.
+    pub name: String,
+    /// The URL of the icon of the guild, if set.
+    pub icon_url: Option<String>,
+}
+
+/// Represents a channel, as returned by `GET_CHANNEL` and `SELECT_VOICE_CHANNEL`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Channel {
+    /// The ID of the channel.
+    pub id: String,
+    /// The ID of the guild of the channel, if it belongs to one.
+    pub guild_id: Option<String>,
+    /// The name of the channel.
+    pub name: String,
+    /// The type of the channel.
+    #[serde(rename = "type")]
+    pub channel_type: u8,
+    /// The topic of the channel, if set.
+    pub topic: Option<String>,
+    /// The bitrate of a voice channel, in bits per second.
+    pub bitrate: Option<u32>,
+    /// The maximum number of users in a voice channel, `0` for no limit.
+    pub user_limit: Option<u32>,
+    /// The position of the channel in its guild.
+    pub position: Option<u32>,
+    /// The users in a voice channel.
+    pub voice_states: Vec<VoiceState>,
+    /// The most recent messages of a text channel.
+    pub messages: Vec<Message>,
+}
+
+/// Represents a user in a voice channel and how the local client plays them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceState {
+    /// The voice state of the user.
+    pub voice_state: VoiceFlags,
+    /// The user.
+    pub user: User,
+    /// The nickname of the user in the guild, if set.
+    pub nick: Option<String>,
+    /// The local playback volume of the user, where `100` is unchanged.
+    pub volume: f64,
+    /// Whether the user is muted locally.
+    pub mute: bool,
+    /// The local stereo panning of the user.
+    pub pan: Pan,
+}
+
+/// Represents whether a user in a voice channel is muted or deafened.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceFlags {
+    /// Whether the user is muted by the server.
+    pub mute: bool,
+    /// Whether the user is deafened by the server.
+    pub deaf: bool,
+    /// Whether the user muted themselves.
+    pub self_mute: bool,
+    /// Whether the user deafened themselves.
+    pub self_deaf: bool,
+    /// Whether the user is suppressed in a stage channel.
+    pub suppress: bool,
+}
+
+/// Represents the stereo panning of a user, from `0.0` to `1.0` on each side.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pan {
+    /// The volume on the left side.
+    pub left: f64,
+    /// The volume on the right side.
+    pub right: f64,
+}
+
+/// Represents a message in a text channel.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Message {
+    /// The ID of the message.
+    pub id: String,
+    /// The author of the message.
+    pub author: Option<User>,
+    /// The nickname of the author in the guild, if set.
+    pub nick: Option<String>,
+    /// The color of the name of the author, as a hex string.
+    pub author_color: Option<String>,
+    /// The content of the message.
+    pub content: String,
+    /// When the message was sent, as an ISO 8601 timestamp.
+    pub timestamp: String,
+    /// When the message was last edited, as an ISO 8601 timestamp.
+    pub edited_timestamp: Option<String>,
+    /// Whether the message was sent as text-to-speech.
+    pub tts: bool,
+    /// Whether the author is blocked by the local user.
+    pub blocked: bool,
+    /// Whether the author is a bot.
+    pub bot: bool,
+    /// Whether the message is pinned.
+    pub pinned: bool,
+    /// Whether the message mentions everyone.
+    pub mention_everyone: bool,
+    /// The users mentioned in the message.
+    pub mentions: Vec<User>,
+    /// The IDs of the roles mentioned in the message.
+    pub mention_roles: Vec<String>,
+    /// The embeds of the message, in their raw form.
+    pub embeds: Vec<serde_json::Value>,
+    /// The attachments of the message, in their raw form.
+    pub attachments: Vec<serde_json::Value>,
+    /// The type of the message.
+    #[serde(rename = "type")]
+    pub message_type: u8,
+}
+
+/// Represents the voice settings of the local user, as returned by `GET_VOICE_SETTINGS`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceSettings {
+    /// The settings of the input device.
+    pub input: VoiceIoSettings,
+    /// The settings of the output device.
+    pub output: VoiceIoSettings,
+    /// How voice input is activated.
+    pub mode: VoiceMode,
+    /// Whether automatic gain control is enabled.
+    pub automatic_gain_control: bool,
+    /// Whether echo cancellation is enabled.
+    pub echo_cancellation: bool,
+    /// Whether noise suppression is enabled.
+    pub noise_suppression: bool,
+    /// Whether voice quality of service is enabled.
+    pub qos: bool,
+    /// Whether a warning is shown when no voice input is detected.
+    pub silence_warning: bool,
+    /// Whether the local user is deafened.
+    pub deaf: bool,
+    /// Whether the local user is muted.
+    pub mute: bool,
+}
+
+/// Represents the settings of an input or output device.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceIoSettings {
+    /// The ID of the selected device.
+    pub device_id: String,
+    /// The volume of the device.
+    pub volume: f64,
+    /// The devices that can be selected.
+    pub available_devices: Vec<AudioDevice>,
+}
+
+impl VoiceIoSettings {
+    /// Returns the selected device, if it is among the available devices.
+    pub fn current_device(&self) -> Option<&AudioDevice> {
+        self.available_devices
+            .iter()
+            .find(|device| device.id == self.device_id)
+    }
+
+    /// Returns the available device called `name`.
+    ///
+    /// An exact match is preferred over one that only differs in case.
+    pub fn device_by_name(&self, name: &str) -> Option<&AudioDevice> {
+        let lowercase = name.to_lowercase();
+        self.available_devices
+            .iter()
+            .find(|device| device.name == name)
+            .or_else(|| {
+                self.available_devices
+                    .iter()
+                    .find(|device| device.name.to_lowercase() == lowercase)
+            })
+    }
+
+    /// Selects the available device called `name` and returns it.
+    ///
+    /// Returns `None` and leaves the selection unchanged if there is no such device.
+    pub fn select_device_by_name(&mut self, name: &str) -> Option<&AudioDevice> {
+        let id = self.device_by_name(name)?.id.clone();
+        self.device_id = id;
+        self.current_device()
+    }
+}
+
+/// Represents an audio device that can be selected for voice.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioDevice {
+    /// The ID of the device.
+    pub id: String,
+    /// The name of the device.
+    pub name: String,
+}
+
+/// Represents how the voice input of the local user is activated.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceMode {
+    /// Whether push-to-talk or voice activity is used.
+    #[serde(rename = "type")]
+    pub mode_type: VoiceModeType,
+    /// Whether the voice activity threshold is set automatically.
+    pub auto_threshold: bool,
+    /// The voice activity threshold, in decibels from `MIN_VOICE_THRESHOLD` to
+    /// `MAX_VOICE_THRESHOLD`.
+    pub threshold: f64,
+    /// The keys that activate push-to-talk.
+    pub shortcut: Vec<ShortcutKey>,
+    /// The push-to-talk release delay, in milliseconds up to `MAX_PTT_DELAY`.
+    pub delay: f64,
+}
+
+impl VoiceMode {
+    /// Validates the voice mode against Discord's limits.
+    pub fn validate(&self) -> Result<(), Error> {
+        check_range(
+            "threshold",
+            self.threshold,
+            MIN_VOICE_THRESHOLD,
+            MAX_VOICE_THRESHOLD,
+        )?;
+        check_range("delay", self.delay, 0.0, MAX_PTT_DELAY)
+    }
+}
+
+/// Represents the way voice input is activated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum VoiceModeType {
+    /// Voice is sent while a shortcut is held.
+    PushToTalk,
+    /// Voice is sent while its volume is above the threshold.
+    #[default]
+    VoiceActivity,
+    /// A mode this version does not know, such as one added by Discord later.
+    ///
+    /// It is serialized as `UNKNOWN`, which Discord rejects, so replace it before
+    /// sending the settings back.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Represents a key in a shortcut.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShortcutKey {
+    /// The kind of key.
+    #[serde(rename = "type")]
+    pub key_type: KeyType,
+    /// The code of the key.
+    pub code: u32,
+    /// The name of the key.
+    pub name: String,
+}
+
+/// Represents the shortcut captured so far, sent in the `CAPTURE_SHORTCUT_CHANGE` event.
+///
+/// Discord only sends the event to connections that started a capture with
+/// `CAPTURE_SHORTCUT`, which requires an authenticated connection.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShortcutChange {
+    /// The keys of the shortcut.
+    pub shortcut: Vec<ShortcutKey>,
+}
+
+impl ShortcutChange {
+    /// Parses a message returned by `Client::poll_events`.
+    ///
+    /// Returns `None` if the message is not a `CAPTURE_SHORTCUT_CHANGE` event.
+    pub fn from_event(event: &serde_json::Value) -> Option<Self> {
+        if event["evt"].as_str() != Some("CAPTURE_SHORTCUT_CHANGE") {
+            return None;
+        }
+        serde_json::from_value(event["data"].clone()).ok()
+    }
+}
+
+/// Represents the kind of a key in a shortcut, serialized as its number.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyType {
+    /// A regular keyboard key.
+    #[default]
+    KeyboardKey,
+    /// A mouse button.
+    MouseButton,
+    /// A keyboard modifier, such as Shift.
+    KeyboardModifierKey,
+    /// A gamepad button.
+    GamepadButton,
+    /// A kind this version does not know, such as one added by Discord later.
+    ///
+    /// The number is kept, so the key is sent back unchanged.
+    Unknown(u8),
+}
+
+impl KeyType {
+    /// Returns the number Discord uses for the kind.
+    pub fn code(self) -> u8 {
+        match self {
+            KeyType::KeyboardKey => 0,
+            KeyType::MouseButton => 1,
+            KeyType::KeyboardModifierKey => 2,
+            KeyType::GamepadButton => 3,
+            KeyType::Unknown(code) => code,
+        }
+    }
+
+    /// Returns the kind with the number `code`.
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0 => KeyType::KeyboardKey,
+            1 => KeyType::MouseButton,
+            2 => KeyType::KeyboardModifierKey,
+            3 => KeyType::GamepadButton,
+            code => KeyType::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for KeyType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u8::deserialize(deserializer).map(KeyType::from_code)
+    }
+}
+
+/// Checks that `value` lies within `min..=max`.
+fn check_range(field: &'static str, value: f64, min: f64, max: f64) -> Result<(), Error> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(Error::ValueOutOfRange {
+            field,
+            value,
+            min,
+            max,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn shortcut_change_parses_only_its_event() {
+        let event = json!({
+            "cmd": "DISPATCH",
+            "evt": "CAPTURE_SHORTCUT_CHANGE",
+            "data": { "shortcut": [{ "type": 0, "code": 17, "name": "ctrl" }] },
+        });
+        let change = ShortcutChange::from_event(&event).unwrap();
+        assert_eq!(change.shortcut.len(), 1);
+        assert_eq!(change.shortcut[0].key_type, KeyType::KeyboardKey);
+        assert_eq!(change.shortcut[0].name, "ctrl");
+
+        let other = json!({ "cmd": "DISPATCH", "evt": "READY", "data": {} });
+        assert!(ShortcutChange::from_event(&other).is_none());
+    }
+
+    #[test]
+    fn unknown_key_types_keep_their_number() {
+        let key: ShortcutKey =
+            serde_json::from_value(json!({ "type": 9, "code": 1, "name": "x" })).unwrap();
+        assert_eq!(key.key_type, KeyType::Unknown(9));
+        assert_eq!(serde_json::to_value(&key).unwrap()["type"], 9);
+
+        let key: ShortcutKey = serde_json::from_value(json!({ "type": 2 })).unwrap();
+        assert_eq!(key.key_type, KeyType::KeyboardModifierKey);
+        assert_eq!(serde_json::to_value(&key).unwrap()["type"], 2);
+    }
+
+    #[test]
+    fn unknown_voice_modes_deserialize_as_unknown() {
+        let mode: VoiceModeType = serde_json::from_value(json!("PUSH_TO_MUTE")).unwrap();
+        assert_eq!(mode, VoiceModeType::Unknown);
+        let mode: VoiceModeType = serde_json::from_value(json!("PUSH_TO_TALK")).unwrap();
+        assert_eq!(mode, VoiceModeType::PushToTalk);
+    }
+}
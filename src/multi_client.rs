@@ -0,0 +1,118 @@
+use crate::activities::Activity;
+use crate::discord_connection::{Client, ClientId};
+use crate::error::Error;
+use std::collections::HashMap;
+
+/// Manages one connection per application for launchers representing several games.
+#[derive(Default)]
+pub struct MultiClient {
+    clients: HashMap<ClientId, Client>,
+}
+
+impl MultiClient {
+    /// Creates a new `MultiClient`.
+    pub fn new() -> Self {
+        MultiClient {
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Connects a client for `client_id`, replacing any existing connection for it.
+    pub fn connect(&mut self, client_id: impl Into<ClientId>) -> Result<(), Error> {
+        let client = Client::new(client_id)?;
+        self.insert(client);
+        Ok(())
+    }
+
+    /// Connects clients for all `client_ids` concurrently.
+    ///
+    /// Returns the client IDs that failed to connect, with their errors.
+    pub fn connect_all<I, C>(&mut self, client_ids: I) -> Vec<(ClientId, Error)>
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<ClientId>,
+    {
+        let client_ids: Vec<ClientId> = client_ids.into_iter().map(Into::into).collect();
+        let results: Vec<(ClientId, Result<Client, Error>)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = client_ids
+                .into_iter()
+                .map(|client_id| {
+                    scope.spawn(move || {
+                        let result = Client::new(client_id.clone());
+                        (client_id, result)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("connection thread panicked"))
+                .collect()
+        });
+
+        let mut failures = Vec::new();
+        for (client_id, result) in results {
+            match result {
+                Ok(client) => {
+                    self.insert(client);
+                }
+                Err(e) => failures.push((client_id, e)),
+            }
+        }
+        failures
+    }
+
+    /// Adds an already connected client, returning the one it replaces.
+    pub fn insert(&mut self, client: Client) -> Option<Client> {
+        self.clients.insert(client.client_id.clone(), client)
+    }
+
+    /// Removes the client for `client_id`.
+    pub fn remove(&mut self, client_id: &ClientId) -> Option<Client> {
+        self.clients.remove(client_id)
+    }
+
+    /// Returns the client for `client_id`.
+    pub fn get(&self, client_id: &ClientId) -> Option<&Client> {
+        self.clients.get(client_id)
+    }
+
+    /// Returns the client for `client_id` mutably.
+    pub fn get_mut(&mut self, client_id: &ClientId) -> Option<&mut Client> {
+        self.clients.get_mut(client_id)
+    }
+
+    /// Returns the IDs of all connected clients.
+    pub fn client_ids(&self) -> impl Iterator<Item = &ClientId> {
+        self.clients.keys()
+    }
+
+    /// Sets the activity for the application `client_id`.
+    pub fn set_activity(&self, client_id: &ClientId, activity: Activity) -> Result<(), Error> {
+        self.client(client_id)?.set_activity(activity)
+    }
+
+    /// Clears the activity for the application `client_id`.
+    pub fn clear_activity(&self, client_id: &ClientId) -> Result<(), Error> {
+        self.client(client_id)?.clear_activity()
+    }
+
+    /// Closes and removes every client, returning the first error encountered.
+    pub fn close_all(&mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+        for (_, client) in self.clients.drain() {
+            if let Err(e) = client.close()
+                && result.is_ok()
+            {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Returns the client for `client_id`, or `Error::UnknownClient`.
+    fn client(&self, client_id: &ClientId) -> Result<&Client, Error> {
+        self.clients
+            .get(client_id)
+            .ok_or_else(|| Error::UnknownClient(client_id.clone()))
+    }
+}
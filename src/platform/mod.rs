@@ -0,0 +1,344 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+mod unix;
+#[cfg(not(any(unix, windows)))]
+mod unsupported;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(unix)]
+pub use unix::UnixIpc;
+#[cfg(target_os = "linux")]
+pub use unix::is_wsl;
+#[cfg(not(any(unix, windows)))]
+pub use unsupported::UnsupportedIpc;
+#[cfg(windows)]
+pub use windows::{DEFAULT_PIPE_BUSY_TIMEOUT, ImpersonationLevel, PipeOptions, WindowsIpc};
+
+/// The IPC transport for the current platform.
+#[cfg(unix)]
+pub type PlatformIpc = UnixIpc;
+/// The IPC transport for the current platform.
+#[cfg(windows)]
+pub type PlatformIpc = WindowsIpc;
+/// The IPC transport for the current platform.
+#[cfg(not(any(unix, windows)))]
+pub type PlatformIpc = UnsupportedIpc;
+
+/// The default range of socket indices to scan.
+pub const DEFAULT_SOCKET_INDICES: Range<u32> = 0..10;
+
+/// The default prefix of the socket names, followed by the index.
+pub const DEFAULT_SOCKET_PREFIX: &str = "discord-ipc-";
+
+/// The default order in which discovery sources are tried.
+pub const DEFAULT_DISCOVERY_ORDER: [DiscoverySource; 4] = [
+    DiscoverySource::Paths,
+    DiscoverySource::Standard,
+    DiscoverySource::Flatpak,
+    DiscoverySource::Snap,
+];
+
+/// The socket directory of the Flatpak build of Discord, relative to `$XDG_RUNTIME_DIR`.
+#[cfg(unix)]
+const FLATPAK_SOCKET_DIR: &str = "app/com.discordapp.Discord";
+
+/// The socket directory of the Snap build of Discord, relative to `$XDG_RUNTIME_DIR`.
+#[cfg(unix)]
+const SNAP_SOCKET_DIR: &str = "snap.discord";
+
+/// Represents a group of candidate paths for the Discord IPC socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoverySource {
+    /// The paths added with `Discovery::add_path`.
+    Paths,
+    /// The standard socket locations of the platform.
+    Standard,
+    /// The socket directory of the Flatpak build of Discord. Unix only.
+    Flatpak,
+    /// The socket directory of the Snap build of Discord. Unix only.
+    Snap,
+}
+
+/// Describes where to look for the Discord IPC socket.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Discovery {
+    /// The range of socket indices to scan.
+    indices: Range<u32>,
+    /// The prefix of the socket names.
+    prefix: String,
+    /// Paths tried in place of `DiscoverySource::Paths`.
+    extra_paths: Vec<PathBuf>,
+    /// The order in which the sources are tried.
+    order: Vec<DiscoverySource>,
+    /// The options used to open the pipes on Windows.
+    #[cfg(windows)]
+    pipe_options: PipeOptions,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Discovery {
+    /// Creates a new `Discovery` with the default scan range and socket names.
+    pub fn new() -> Self {
+        Discovery {
+            indices: DEFAULT_SOCKET_INDICES,
+            prefix: DEFAULT_SOCKET_PREFIX.to_string(),
+            extra_paths: Vec::new(),
+            order: DEFAULT_DISCOVERY_ORDER.to_vec(),
+            #[cfg(windows)]
+            pipe_options: PipeOptions::new(),
+        }
+    }
+
+    /// Sets the range of socket indices to scan.
+    pub fn set_indices(mut self, indices: Range<u32>) -> Self {
+        self.indices = indices;
+        self
+    }
+
+    /// Sets the prefix of the socket names.
+    pub fn set_prefix(mut self, prefix: String) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Adds a path to try with `DiscoverySource::Paths`.
+    pub fn add_path(mut self, path: PathBuf) -> Self {
+        self.extra_paths.push(path);
+        self
+    }
+
+    /// Sets the order in which the sources are tried.
+    ///
+    /// Sources left out are not tried at all. Defaults to `DEFAULT_DISCOVERY_ORDER`.
+    pub fn set_order(mut self, order: Vec<DiscoverySource>) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Sets the options used to open the pipes on Windows.
+    ///
+    /// Useful where policy requires a specific impersonation level for named pipes.
+    #[cfg(windows)]
+    pub fn set_pipe_options(mut self, pipe_options: PipeOptions) -> Self {
+        self.pipe_options = pipe_options;
+        self
+    }
+
+    /// Returns the options used to open the pipes on Windows.
+    #[cfg(windows)]
+    pub fn pipe_options(&self) -> &PipeOptions {
+        &self.pipe_options
+    }
+
+    /// Returns every path that is tried, in order.
+    pub fn candidates(&self) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        for source in &self.order {
+            match source {
+                DiscoverySource::Paths => candidates.extend(self.extra_paths.iter().cloned()),
+                DiscoverySource::Standard => candidates.extend(self.standard_candidates()),
+                #[cfg(unix)]
+                DiscoverySource::Flatpak => {
+                    candidates.extend(self.sandbox_candidates(FLATPAK_SOCKET_DIR))
+                }
+                #[cfg(unix)]
+                DiscoverySource::Snap => {
+                    candidates.extend(self.sandbox_candidates(SNAP_SOCKET_DIR))
+                }
+                #[cfg(not(unix))]
+                DiscoverySource::Flatpak | DiscoverySource::Snap => {}
+            }
+        }
+        candidates
+    }
+
+    /// Returns the candidates in the standard socket locations of the platform.
+    fn standard_candidates(&self) -> Vec<PathBuf> {
+        #[cfg_attr(not(any(unix, windows)), allow(unused_mut))]
+        let mut candidates = Vec::new();
+
+        #[cfg(unix)]
+        for dir in unix::socket_dirs() {
+            for i in self.indices.clone() {
+                candidates.push(dir.join(format!("{}{}", self.prefix, i)));
+            }
+        }
+
+        #[cfg(windows)]
+        for i in self.indices.clone() {
+            candidates.push(PathBuf::from(format!(r"\\.\pipe\{}{}", self.prefix, i)));
+        }
+
+        candidates
+    }
+
+    /// Returns the candidates in a sandbox directory under `$XDG_RUNTIME_DIR`.
+    #[cfg(unix)]
+    fn sandbox_candidates(&self, relative: &str) -> Vec<PathBuf> {
+        let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") else {
+            return Vec::new();
+        };
+        let dir = PathBuf::from(runtime_dir).join(relative);
+        self.indices
+            .clone()
+            .map(|i| dir.join(format!("{}{}", self.prefix, i)))
+            .collect()
+    }
+
+    /// Removes the stale sockets among the candidates and returns their paths.
+    ///
+    /// Only sockets owned by the current user that refuse connections are removed,
+    /// such as those left behind by a crashed Discord. Named pipes on Windows vanish
+    /// with their server, so nothing is removed there.
+    pub fn remove_stale_sockets(&self) -> Vec<PathBuf> {
+        #[cfg(unix)]
+        return self
+            .candidates()
+            .into_iter()
+            .filter(|path| unix::remove_if_stale(path))
+            .collect();
+        #[cfg(not(unix))]
+        Vec::new()
+    }
+
+    /// Probes every candidate path and reports what was found at each one.
+    ///
+    /// On Unix, only the file type and permissions of each socket are checked, so
+    /// nothing is connected to and a stale socket is reported as available. On
+    /// Windows, each pipe is opened and closed again, which Discord sees as a client
+    /// that disconnects before its handshake.
+    pub fn diagnose(&self) -> Vec<Probe> {
+        diagnose_paths(self.candidates())
+    }
+}
+
+/// Probes every path in `paths` and reports what was found at each one.
+pub(crate) fn diagnose_paths(paths: Vec<PathBuf>) -> Vec<Probe> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let status = probe(&path);
+            Probe { path, status }
+        })
+        .collect()
+}
+
+/// Represents the outcome of probing a single candidate path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProbeStatus {
+    /// A server can be connected to at the path.
+    ///
+    /// On Unix, this means a socket exists that the current user may connect to,
+    /// whether or not anything listens on it.
+    Available,
+    /// Nothing exists at the path.
+    NotFound,
+    /// The path exists but the current user may not connect to it.
+    PermissionDenied,
+    /// The path exists but nothing is listening, usually left by a crashed Discord.
+    ///
+    /// Telling this apart requires connecting, so `diagnose` does not report it.
+    Stale,
+    /// The server exists but is busy serving another connection.
+    Busy,
+    /// Connecting failed for another reason.
+    Other(String),
+}
+
+impl std::fmt::Display for ProbeStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeStatus::Available => f.write_str("available"),
+            ProbeStatus::NotFound => f.write_str("not found"),
+            ProbeStatus::PermissionDenied => f.write_str("permission denied"),
+            ProbeStatus::Stale => f.write_str("stale (connection refused)"),
+            ProbeStatus::Busy => f.write_str("busy"),
+            ProbeStatus::Other(error) => write!(f, "error: {}", error),
+        }
+    }
+}
+
+/// Represents a candidate path and the outcome of probing it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+    /// The path that was probed.
+    pub path: PathBuf,
+    /// What was found at the path.
+    pub status: ProbeStatus,
+}
+
+impl std::fmt::Display for Probe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.status)
+    }
+}
+
+/// Probes a single candidate path.
+fn probe(path: &Path) -> ProbeStatus {
+    #[cfg(unix)]
+    return unix::probe(path);
+    #[cfg(windows)]
+    return windows::probe(path);
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        ProbeStatus::Other("unsupported platform".to_string())
+    }
+}
+
+/// A trait for Inter-Process Communication (IPC).
+pub trait Ipc: Read + Write + Send + Sync {
+    /// Connects to the IPC server using the default discovery.
+    fn connect() -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        Self::connect_with(&Discovery::new())
+    }
+
+    /// Connects to the first available IPC server among the discovery candidates.
+    fn connect_with(discovery: &Discovery) -> Result<Self, Error>
+    where
+        Self: Sized;
+
+    /// Moves the connection into or out of non-blocking mode.
+    ///
+    /// Transports that cannot be made non-blocking return an `Unsupported` error.
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        let _ = nonblocking;
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    /// Returns the raw file descriptor of the connection, if it has one.
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<std::os::fd::RawFd> {
+        None
+    }
+
+    /// Returns the raw handle of the connection, if it has one.
+    #[cfg(windows)]
+    fn raw_handle(&self) -> Option<std::os::windows::io::RawHandle> {
+        None
+    }
+
+    /// Sets how long reads wait for data, or clears the timeout with `None`.
+    ///
+    /// Transports without read timeouts return an `Unsupported` error.
+    fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+        let _ = timeout;
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
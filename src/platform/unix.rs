@@ -0,0 +1,239 @@
+use super::{Discovery, Ipc, ProbeStatus};
+use crate::error::Error;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+/// A struct for Unix IPC.
+pub struct UnixIpc(UnixStream);
+
+impl UnixIpc {
+    /// Connects to the Discord IPC socket at `path`.
+    pub fn connect_path(path: &Path) -> Result<Self, Error> {
+        Ok(Self(UnixStream::connect(path)?))
+    }
+
+    /// Consumes the transport and returns the underlying stream.
+    pub fn into_inner(self) -> UnixStream {
+        self.0
+    }
+}
+
+/// Returns whether the process is running under the Windows Subsystem for Linux.
+#[cfg(target_os = "linux")]
+pub fn is_wsl() -> bool {
+    std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .is_ok_and(|release| release.to_ascii_lowercase().contains("microsoft"))
+}
+
+impl Ipc for UnixIpc {
+    /// Connects to the Discord IPC server on Unix.
+    fn connect_with(discovery: &Discovery) -> Result<Self, Error> {
+        for path in discovery.candidates() {
+            if path.exists() {
+                return Self::connect_path(&path);
+            }
+        }
+        Err(Error::ConnectionNotFound)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+        self.0.set_read_timeout(timeout)
+    }
+
+    fn raw_fd(&self) -> Option<RawFd> {
+        Some(self.0.as_raw_fd())
+    }
+}
+
+impl AsRawFd for UnixIpc {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixIpc {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+/// Probes a candidate socket path without connecting to it.
+///
+/// Only the file type and permissions are checked, so a socket left behind by a
+/// crashed Discord is reported as available.
+pub(super) fn probe(path: &Path) -> ProbeStatus {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::FileTypeExt;
+
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return match e.kind() {
+                std::io::ErrorKind::NotFound => ProbeStatus::NotFound,
+                std::io::ErrorKind::PermissionDenied => ProbeStatus::PermissionDenied,
+                _ => ProbeStatus::Other(e.to_string()),
+            };
+        }
+    };
+    if !metadata.file_type().is_socket() {
+        return ProbeStatus::Other("not a socket".to_string());
+    }
+
+    // Connecting to a socket requires write permission on it.
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return ProbeStatus::Other("path contains a NUL byte".to_string());
+    };
+    // SAFETY: `path` is a NUL-terminated string that outlives the call.
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK) } == 0 {
+        ProbeStatus::Available
+    } else {
+        ProbeStatus::PermissionDenied
+    }
+}
+
+/// Connects to a candidate socket path to find out whether anything listens on it.
+fn connect_probe(path: &Path) -> ProbeStatus {
+    match UnixStream::connect(path) {
+        Ok(_) => ProbeStatus::Available,
+        Err(e) => match e.kind() {
+            std::io::ErrorKind::NotFound => ProbeStatus::NotFound,
+            std::io::ErrorKind::PermissionDenied => ProbeStatus::PermissionDenied,
+            std::io::ErrorKind::ConnectionRefused => ProbeStatus::Stale,
+            _ => ProbeStatus::Other(e.to_string()),
+        },
+    }
+}
+
+/// Removes `path` if it is a stale socket owned by the current user.
+///
+/// Returns whether the socket was removed.
+pub(super) fn remove_if_stale(path: &Path) -> bool {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return false;
+    };
+    // SAFETY: `getuid` has no preconditions and cannot fail.
+    let uid = unsafe { libc::getuid() };
+    if !metadata.file_type().is_socket() || metadata.uid() != uid {
+        return false;
+    }
+    connect_probe(path) == ProbeStatus::Stale && std::fs::remove_file(path).is_ok()
+}
+
+/// Returns the directories that may contain the Discord IPC socket, in search order.
+pub(super) fn socket_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    #[cfg(target_os = "macos")]
+    dirs.extend(darwin_user_temp_dir());
+
+    dirs.extend(
+        ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .map(PathBuf::from),
+    );
+
+    #[cfg(any(
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly",
+        target_os = "illumos",
+        target_os = "solaris"
+    ))]
+    {
+        // SAFETY: `getuid` has no preconditions and cannot fail.
+        let uid = unsafe { libc::getuid() };
+        dirs.push(PathBuf::from(format!("/var/run/user/{}", uid)));
+    }
+
+    dirs.push(PathBuf::from("/tmp"));
+    dirs.dedup();
+    dirs
+}
+
+/// Returns the per-user temporary directory on macOS.
+///
+/// Unlike `$TMPDIR`, this is not redirected into the container of a sandboxed host app.
+#[cfg(target_os = "macos")]
+fn darwin_user_temp_dir() -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+
+    // SAFETY: A null buffer with length zero only queries the required size.
+    let len = unsafe { libc::confstr(libc::_CS_DARWIN_USER_TEMP_DIR, std::ptr::null_mut(), 0) };
+    if len == 0 {
+        return None;
+    }
+
+    let mut buf = vec![0u8; len];
+    // SAFETY: `buf` is valid for writes of `len` bytes.
+    let written = unsafe {
+        libc::confstr(
+            libc::_CS_DARWIN_USER_TEMP_DIR,
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+        )
+    };
+    if written == 0 || written > buf.len() {
+        return None;
+    }
+
+    // Drop the trailing NUL terminator.
+    buf.truncate(written - 1);
+    Some(PathBuf::from(std::ffi::OsString::from_vec(buf)))
+}
+
+impl Read for UnixIpc {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for UnixIpc {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn probe_checks_the_file_without_connecting() {
+        let path = std::env::temp_dir().join(format!(
+            "discord-presence-probe-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(probe(&path), ProbeStatus::NotFound);
+
+        let listener = UnixListener::bind(&path).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        assert_eq!(probe(&path), ProbeStatus::Available);
+        assert!(listener.accept().is_err());
+
+        // The socket file outlives its listener.
+        drop(listener);
+        assert_eq!(probe(&path), ProbeStatus::Available);
+        assert_eq!(connect_probe(&path), ProbeStatus::Stale);
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(probe(&path), ProbeStatus::Other("not a socket".to_string()));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
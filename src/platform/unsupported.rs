@@ -0,0 +1,37 @@
+use super::{Discovery, Ipc};
+use crate::error::Error;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// A placeholder transport for platforms without Discord IPC support.
+///
+/// It can never be connected; connecting always returns `Error::UnsupportedPlatform`.
+pub struct UnsupportedIpc(());
+
+impl UnsupportedIpc {
+    /// Always returns `Error::UnsupportedPlatform`.
+    pub fn connect_path(_path: &Path) -> Result<Self, Error> {
+        Err(Error::UnsupportedPlatform)
+    }
+}
+
+impl Ipc for UnsupportedIpc {
+    fn connect_with(_discovery: &Discovery) -> Result<Self, Error> {
+        Err(Error::UnsupportedPlatform)
+    }
+}
+
+impl Read for UnsupportedIpc {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl Write for UnsupportedIpc {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
@@ -0,0 +1,255 @@
+use super::{Discovery, Ipc, ProbeStatus};
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::fs::OpenOptionsExt;
+use std::os::windows::io::{AsHandle, AsRawHandle, BorrowedHandle, RawHandle};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// The default time to wait for a busy pipe to accept another connection.
+pub const DEFAULT_PIPE_BUSY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The Win32 error returned when all instances of a pipe are busy.
+const ERROR_PIPE_BUSY: i32 = 231;
+
+/// The `SECURITY_CONTEXT_TRACKING` flag of `CreateFileW`.
+const SECURITY_CONTEXT_TRACKING: u32 = 0x0004_0000;
+
+/// The `SECURITY_EFFECTIVE_ONLY` flag of `CreateFileW`.
+const SECURITY_EFFECTIVE_ONLY: u32 = 0x0008_0000;
+
+/// Represents how far the Discord process may impersonate this one over the pipe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImpersonationLevel {
+    /// The server can neither identify nor impersonate the client.
+    Anonymous,
+    /// The server can identify the client but not impersonate it.
+    Identification,
+    /// The server can impersonate the client on the local system.
+    Impersonation,
+    /// The server can impersonate the client on remote systems too.
+    Delegation,
+}
+
+impl ImpersonationLevel {
+    /// Returns the `SECURITY_*` flag of `CreateFileW` for this level.
+    fn flag(self) -> u32 {
+        match self {
+            ImpersonationLevel::Anonymous => 0,
+            ImpersonationLevel::Identification => 1 << 16,
+            ImpersonationLevel::Impersonation => 2 << 16,
+            ImpersonationLevel::Delegation => 3 << 16,
+        }
+    }
+}
+
+/// Represents the options used to open the Discord IPC pipe.
+///
+/// By default, no security quality of service is requested and Windows applies its own
+/// defaults. If every instance of the pipe is busy, opening waits for one with
+/// `WaitNamedPipeW` for up to `DEFAULT_PIPE_BUSY_TIMEOUT`.
+///
+/// The pipe is always opened for synchronous I/O, without `FILE_FLAG_OVERLAPPED`, since
+/// it is used through `std::fs::File`. As a result, reads cannot time out or be made
+/// non-blocking, so `Client::ping`, `Client::poll_events` and `Client::try_set_activity`
+/// return an `Unsupported` I/O error on Windows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipeOptions {
+    /// The impersonation level granted to the server, if one is requested.
+    impersonation_level: Option<ImpersonationLevel>,
+    /// Whether the server sees later changes to the security context of the client.
+    context_tracking: bool,
+    /// Whether the server only sees the enabled parts of the client's security context.
+    effective_only: bool,
+    /// How long to wait for a busy pipe, in milliseconds.
+    busy_timeout_ms: u32,
+}
+
+impl Default for PipeOptions {
+    fn default() -> Self {
+        PipeOptions {
+            impersonation_level: None,
+            context_tracking: false,
+            effective_only: false,
+            busy_timeout_ms: DEFAULT_PIPE_BUSY_TIMEOUT.as_millis() as u32,
+        }
+    }
+}
+
+impl PipeOptions {
+    /// Creates a new `PipeOptions` with the Windows defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long to wait for a pipe whose instances are all busy.
+    ///
+    /// With a zero timeout, a busy pipe fails right away. Defaults to
+    /// `DEFAULT_PIPE_BUSY_TIMEOUT`.
+    pub fn set_busy_timeout(mut self, busy_timeout: Duration) -> Self {
+        self.busy_timeout_ms = u32::try_from(busy_timeout.as_millis()).unwrap_or(u32::MAX);
+        self
+    }
+
+    /// Sets the impersonation level granted to the server.
+    pub fn set_impersonation_level(mut self, impersonation_level: ImpersonationLevel) -> Self {
+        self.impersonation_level = Some(impersonation_level);
+        self
+    }
+
+    /// Sets whether the server sees later changes to the security context of the client.
+    ///
+    /// Only takes effect together with an impersonation level.
+    pub fn set_context_tracking(mut self, context_tracking: bool) -> Self {
+        self.context_tracking = context_tracking;
+        self
+    }
+
+    /// Sets whether the server only sees the enabled parts of the client's security
+    /// context.
+    ///
+    /// Only takes effect together with an impersonation level.
+    pub fn set_effective_only(mut self, effective_only: bool) -> Self {
+        self.effective_only = effective_only;
+        self
+    }
+
+    /// Returns the security quality-of-service flags of `CreateFileW`, if any are set.
+    fn security_qos_flags(&self) -> Option<u32> {
+        let mut flags = self.impersonation_level?.flag();
+        if self.context_tracking {
+            flags |= SECURITY_CONTEXT_TRACKING;
+        }
+        if self.effective_only {
+            flags |= SECURITY_EFFECTIVE_ONLY;
+        }
+        Some(flags)
+    }
+}
+
+/// A struct for Windows IPC.
+pub struct WindowsIpc(File);
+
+impl WindowsIpc {
+    /// Connects to the Discord IPC pipe at `path`.
+    pub fn connect_path(path: &Path) -> Result<Self, Error> {
+        Self::connect_path_with(path, &PipeOptions::default())
+    }
+
+    /// Connects to the Discord IPC pipe at `path`, opening it with `options`.
+    ///
+    /// While every instance of the pipe is busy, waits for one to become available
+    /// until the busy timeout of `options` elapses.
+    pub fn connect_path_with(path: &Path, options: &PipeOptions) -> Result<Self, Error> {
+        let mut open_options = std::fs::OpenOptions::new();
+        open_options.read(true).write(true);
+        if let Some(flags) = options.security_qos_flags() {
+            open_options.security_qos_flags(flags);
+        }
+
+        let deadline = Instant::now() + Duration::from_millis(options.busy_timeout_ms.into());
+        loop {
+            match open_options.open(path) {
+                Ok(file) => return Ok(Self(file)),
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() || !wait_for_pipe(path, remaining) {
+                        return Err(e.into());
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Consumes the transport and returns the underlying pipe.
+    pub fn into_inner(self) -> File {
+        self.0
+    }
+}
+
+impl Ipc for WindowsIpc {
+    /// Connects to the Discord IPC server on Windows, using the pipe options of
+    /// `discovery`.
+    fn connect_with(discovery: &Discovery) -> Result<Self, Error> {
+        for path in discovery.candidates() {
+            if let Ok(ipc) = Self::connect_path_with(&path, discovery.pipe_options()) {
+                return Ok(ipc);
+            }
+        }
+        Err(Error::ConnectionNotFound)
+    }
+
+    fn raw_handle(&self) -> Option<RawHandle> {
+        Some(self.0.as_raw_handle())
+    }
+}
+
+impl AsRawHandle for WindowsIpc {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.0.as_raw_handle()
+    }
+}
+
+impl AsHandle for WindowsIpc {
+    fn as_handle(&self) -> BorrowedHandle<'_> {
+        self.0.as_handle()
+    }
+}
+
+/// Waits up to `timeout` for an instance of the pipe at `path` to become available.
+///
+/// Returns whether one did. Another client may still take it first.
+fn wait_for_pipe(path: &Path, timeout: Duration) -> bool {
+    let name: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    // A zero timeout would mean the default timeout of the pipe.
+    let timeout_ms = u32::try_from(timeout.as_millis())
+        .unwrap_or(u32::MAX)
+        .max(1);
+    // SAFETY: `name` is a NUL-terminated wide string that outlives the call.
+    unsafe { windows_sys::Win32::System::Pipes::WaitNamedPipeW(name.as_ptr(), timeout_ms) != 0 }
+}
+
+/// Probes a candidate pipe path.
+///
+/// The pipe is opened and closed again, which takes up one of the server's pipe
+/// instances while it lasts.
+pub(super) fn probe(path: &Path) -> ProbeStatus {
+    match std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+    {
+        Ok(_) => ProbeStatus::Available,
+        Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => ProbeStatus::Busy,
+        Err(e) => match e.kind() {
+            std::io::ErrorKind::NotFound => ProbeStatus::NotFound,
+            std::io::ErrorKind::PermissionDenied => ProbeStatus::PermissionDenied,
+            _ => ProbeStatus::Other(e.to_string()),
+        },
+    }
+}
+
+impl Read for WindowsIpc {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for WindowsIpc {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
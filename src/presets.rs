@@ -0,0 +1,661 @@
+use crate::activities::{Activity, ActivityType, Assets, Party, Redacted, Secrets, Timestamps};
+use std::time::{Duration, Instant};
+
+/// Represents a music track being played, for use as a Listening activity.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TrackPresence {
+    /// The title of the track.
+    title: String,
+    /// The artist of the track.
+    artist: Option<String>,
+    /// The album of the track.
+    album: Option<String>,
+    /// The URL of the album artwork.
+    artwork_url: Option<String>,
+    /// The current playback position.
+    position: Option<Duration>,
+    /// The total duration of the track.
+    duration: Option<Duration>,
+}
+
+impl TrackPresence {
+    /// Creates a new `TrackPresence`.
+    pub fn new(title: String) -> Self {
+        TrackPresence {
+            title,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the artist of the track.
+    pub fn set_artist(mut self, artist: String) -> Self {
+        self.artist = Some(artist);
+        self
+    }
+
+    /// Sets the album of the track.
+    pub fn set_album(mut self, album: String) -> Self {
+        self.album = Some(album);
+        self
+    }
+
+    /// Sets the URL of the album artwork.
+    pub fn set_artwork_url(mut self, artwork_url: String) -> Self {
+        self.artwork_url = Some(artwork_url);
+        self
+    }
+
+    /// Sets the current playback position.
+    pub fn set_position(mut self, position: Duration) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    /// Sets the total duration of the track.
+    pub fn set_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Converts the track into a Listening activity, anchoring the timestamps to now.
+    pub fn to_activity(&self) -> Activity {
+        let mut activity = Activity::new()
+            .set_activity_type(ActivityType::Listening)
+            .set_details(self.title.clone());
+
+        if let Some(artist) = &self.artist {
+            activity = activity.set_state(format!("by {}", artist));
+        }
+
+        if self.artwork_url.is_some() || self.album.is_some() {
+            let mut assets = Assets::new();
+            if let Some(artwork_url) = &self.artwork_url {
+                assets = assets.set_large_image(artwork_url.clone());
+            }
+            if let Some(album) = &self.album {
+                assets = assets.set_large_text(album.clone());
+            }
+            activity = activity.set_assets(assets);
+        }
+
+        if let Some(timestamps) = progress_timestamps(self.position, self.duration) {
+            activity = activity.set_timestamps(timestamps);
+        }
+
+        activity
+    }
+}
+
+impl From<TrackPresence> for Activity {
+    fn from(track: TrackPresence) -> Self {
+        track.to_activity()
+    }
+}
+
+/// Represents a video being watched, for use as a Watching activity.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WatchingPresence {
+    /// The title of the video or show.
+    title: String,
+    /// The season number of the episode.
+    season: Option<u32>,
+    /// The episode number.
+    episode: Option<u32>,
+    /// The URL of the poster image.
+    poster_url: Option<String>,
+    /// The current playback position.
+    position: Option<Duration>,
+    /// The total duration of the video.
+    duration: Option<Duration>,
+    /// Whether playback is paused.
+    paused: bool,
+}
+
+impl WatchingPresence {
+    /// Creates a new `WatchingPresence`.
+    pub fn new(title: String) -> Self {
+        WatchingPresence {
+            title,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the season number of the episode.
+    pub fn set_season(mut self, season: u32) -> Self {
+        self.season = Some(season);
+        self
+    }
+
+    /// Sets the episode number.
+    pub fn set_episode(mut self, episode: u32) -> Self {
+        self.episode = Some(episode);
+        self
+    }
+
+    /// Sets the URL of the poster image.
+    pub fn set_poster_url(mut self, poster_url: String) -> Self {
+        self.poster_url = Some(poster_url);
+        self
+    }
+
+    /// Sets the current playback position.
+    pub fn set_position(mut self, position: Duration) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    /// Sets the total duration of the video.
+    pub fn set_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Sets whether playback is paused.
+    pub fn set_paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+
+    /// Converts the video into a Watching activity, anchoring the timestamps to now.
+    ///
+    /// While paused, the timestamps are omitted so Discord does not keep counting.
+    pub fn to_activity(&self) -> Activity {
+        let mut activity = Activity::new()
+            .set_activity_type(ActivityType::Watching)
+            .set_details(self.title.clone());
+
+        let episode = match (self.season, self.episode) {
+            (Some(season), Some(episode)) => Some(format!("S{}E{}", season, episode)),
+            (Some(season), None) => Some(format!("Season {}", season)),
+            (None, Some(episode)) => Some(format!("Episode {}", episode)),
+            (None, None) => None,
+        };
+        let state = match (episode, self.paused) {
+            (Some(episode), true) => Some(format!("{} (Paused)", episode)),
+            (Some(episode), false) => Some(episode),
+            (None, true) => Some("Paused".to_string()),
+            (None, false) => None,
+        };
+        if let Some(state) = state {
+            activity = activity.set_state(state);
+        }
+
+        if let Some(poster_url) = &self.poster_url {
+            activity = activity.set_assets(Assets::new().set_large_image(poster_url.clone()));
+        }
+
+        if !self.paused
+            && let Some(timestamps) = progress_timestamps(self.position, self.duration)
+        {
+            activity = activity.set_timestamps(timestamps);
+        }
+
+        activity
+    }
+}
+
+impl From<WatchingPresence> for Activity {
+    fn from(video: WatchingPresence) -> Self {
+        video.to_activity()
+    }
+}
+
+/// Represents a game session, for use as a Playing activity.
+///
+/// The `Debug` output redacts the join secret.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct GamePresence {
+    /// The map being played.
+    map: Option<String>,
+    /// The game mode being played.
+    mode: Option<String>,
+    /// The current score.
+    score: Option<String>,
+    /// The player's rank.
+    rank: Option<String>,
+    /// The ID of the player's party.
+    party_id: Option<String>,
+    /// The current and maximum size of the party.
+    party_size: Option<(u32, u32)>,
+    /// The secret for joining the party.
+    join_secret: Option<String>,
+    /// Whether other users can join the party.
+    joinable: bool,
+}
+
+impl std::fmt::Debug for GamePresence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GamePresence")
+            .field("map", &self.map)
+            .field("mode", &self.mode)
+            .field("score", &self.score)
+            .field("rank", &self.rank)
+            .field("party_id", &self.party_id)
+            .field("party_size", &self.party_size)
+            .field("join_secret", &self.join_secret.as_ref().map(|_| Redacted))
+            .field("joinable", &self.joinable)
+            .finish()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for GamePresence {
+    /// Wipes the join secret from memory.
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.join_secret);
+    }
+}
+
+impl GamePresence {
+    /// Creates a new `GamePresence`.
+    pub fn new() -> Self {
+        GamePresence::default()
+    }
+
+    /// Sets the map being played.
+    pub fn set_map(mut self, map: String) -> Self {
+        self.map = Some(map);
+        self
+    }
+
+    /// Sets the game mode being played.
+    pub fn set_mode(mut self, mode: String) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Sets the current score.
+    pub fn set_score(mut self, score: String) -> Self {
+        self.score = Some(score);
+        self
+    }
+
+    /// Sets the player's rank.
+    pub fn set_rank(mut self, rank: String) -> Self {
+        self.rank = Some(rank);
+        self
+    }
+
+    /// Sets the ID of the player's party.
+    pub fn set_party_id(mut self, party_id: String) -> Self {
+        self.party_id = Some(party_id);
+        self
+    }
+
+    /// Sets the current and maximum size of the party.
+    pub fn set_party_size(mut self, current: u32, max: u32) -> Self {
+        self.party_size = Some((current, max));
+        self
+    }
+
+    /// Sets the secret for joining the party.
+    pub fn set_join_secret(mut self, join_secret: String) -> Self {
+        self.join_secret = Some(join_secret);
+        self
+    }
+
+    /// Sets whether other users can join the party.
+    pub fn set_joinable(mut self, joinable: bool) -> Self {
+        self.joinable = joinable;
+        self
+    }
+
+    /// Converts the game session into a Playing activity.
+    ///
+    /// The join secret is only included when the session is joinable and has a party ID.
+    pub fn to_activity(&self) -> Activity {
+        let mut activity = Activity::new().set_activity_type(ActivityType::Playing);
+
+        let details = match (&self.mode, &self.map) {
+            (Some(mode), Some(map)) => Some(format!("{} - {}", mode, map)),
+            (Some(mode), None) => Some(mode.clone()),
+            (None, Some(map)) => Some(map.clone()),
+            (None, None) => None,
+        };
+        if let Some(details) = details {
+            activity = activity.set_details(details);
+        }
+
+        let state = match (&self.score, &self.rank) {
+            (Some(score), Some(rank)) => Some(format!("Score: {} | Rank: {}", score, rank)),
+            (Some(score), None) => Some(format!("Score: {}", score)),
+            (None, Some(rank)) => Some(format!("Rank: {}", rank)),
+            (None, None) => None,
+        };
+        if let Some(state) = state {
+            activity = activity.set_state(state);
+        }
+
+        if self.party_id.is_some() || self.party_size.is_some() {
+            let mut party = Party::new();
+            if let Some(party_id) = &self.party_id {
+                party = party.set_id(party_id.clone());
+            }
+            if let Some((current, max)) = self.party_size {
+                party = party.set_size(current, max);
+            }
+            activity = activity.set_party(party);
+        }
+
+        if self.joinable
+            && self.party_id.is_some()
+            && let Some(join_secret) = &self.join_secret
+        {
+            activity = activity.set_secrets(Secrets::new().set_join(join_secret.clone()));
+        }
+
+        activity
+    }
+}
+
+impl From<GamePresence> for Activity {
+    fn from(game: GamePresence) -> Self {
+        game.to_activity()
+    }
+}
+
+/// The default template for the details line of an `IdePresence`.
+pub const DEFAULT_IDE_DETAILS_TEMPLATE: &str = "Editing {file}";
+
+/// The default template for the state line of an `IdePresence`.
+pub const DEFAULT_IDE_STATE_TEMPLATE: &str = "Workspace: {workspace}";
+
+/// The default interval used by a `Debouncer`.
+pub const DEFAULT_DEBOUNCE_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Represents an editor session, for use by editor and IDE plugins.
+///
+/// Templates may reference `{workspace}`, `{file}`, `{language}` and `{problems}`.
+/// A line whose template references a missing value is left out.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IdePresence {
+    /// The name of the open workspace.
+    workspace: Option<String>,
+    /// The name of the file being edited.
+    file: Option<String>,
+    /// The language of the file being edited.
+    language: Option<String>,
+    /// The number of problems reported in the workspace.
+    problems: Option<u32>,
+    /// Whether the editor is idle.
+    idle: bool,
+    /// The template for the details line.
+    details_template: String,
+    /// The template for the state line.
+    state_template: String,
+    /// The text shown as details while idle.
+    idle_text: String,
+}
+
+impl Default for IdePresence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdePresence {
+    /// Creates a new `IdePresence`.
+    pub fn new() -> Self {
+        IdePresence {
+            workspace: None,
+            file: None,
+            language: None,
+            problems: None,
+            idle: false,
+            details_template: DEFAULT_IDE_DETAILS_TEMPLATE.to_string(),
+            state_template: DEFAULT_IDE_STATE_TEMPLATE.to_string(),
+            idle_text: "Idling".to_string(),
+        }
+    }
+
+    /// Sets the name of the open workspace.
+    pub fn set_workspace(mut self, workspace: String) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Sets the name of the file being edited.
+    pub fn set_file(mut self, file: String) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    /// Sets the language of the file being edited.
+    pub fn set_language(mut self, language: String) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    /// Sets the number of problems reported in the workspace.
+    pub fn set_problems(mut self, problems: u32) -> Self {
+        self.problems = Some(problems);
+        self
+    }
+
+    /// Sets whether the editor is idle.
+    pub fn set_idle(mut self, idle: bool) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// Sets the template for the details line.
+    pub fn set_details_template(mut self, details_template: String) -> Self {
+        self.details_template = details_template;
+        self
+    }
+
+    /// Sets the template for the state line.
+    pub fn set_state_template(mut self, state_template: String) -> Self {
+        self.state_template = state_template;
+        self
+    }
+
+    /// Sets the text shown as details while idle.
+    pub fn set_idle_text(mut self, idle_text: String) -> Self {
+        self.idle_text = idle_text;
+        self
+    }
+
+    /// Converts the editor session into a Playing activity.
+    ///
+    /// The language is used as the large image key, lowercased.
+    pub fn to_activity(&self) -> Activity {
+        let mut activity = Activity::new().set_activity_type(ActivityType::Playing);
+
+        let details = if self.idle {
+            Some(self.idle_text.clone())
+        } else {
+            self.render(&self.details_template)
+        };
+        if let Some(details) = details {
+            activity = activity.set_details(details);
+        }
+
+        if let Some(mut state) = self.render(&self.state_template) {
+            if let Some(problems) = self.problems.filter(|problems| *problems > 0)
+                && !self.state_template.contains("{problems}")
+            {
+                state = format!("{} ({} problems)", state, problems);
+            }
+            activity = activity.set_state(state);
+        }
+
+        if let Some(language) = &self.language {
+            activity = activity.set_assets(
+                Assets::new()
+                    .set_large_image(language.to_lowercase())
+                    .set_large_text(language.clone()),
+            );
+        }
+
+        activity
+    }
+
+    /// Renders a template, returning `None` if it references a missing value.
+    fn render(&self, template: &str) -> Option<String> {
+        let problems = self.problems.map(|problems| problems.to_string());
+        let values = [
+            ("{workspace}", self.workspace.as_deref()),
+            ("{file}", self.file.as_deref()),
+            ("{language}", self.language.as_deref()),
+            ("{problems}", problems.as_deref()),
+        ];
+
+        let mut rendered = template.to_string();
+        for (placeholder, value) in values {
+            if rendered.contains(placeholder) {
+                rendered = rendered.replace(placeholder, value?);
+            }
+        }
+        Some(rendered)
+    }
+}
+
+impl From<IdePresence> for Activity {
+    fn from(ide: IdePresence) -> Self {
+        ide.to_activity()
+    }
+}
+
+/// Coalesces rapid activity changes so that at most one update is sent per interval.
+#[derive(Clone, Debug)]
+pub struct Debouncer {
+    /// The minimum time between two emitted activities.
+    interval: Duration,
+    /// When the last activity was emitted.
+    last_emit: Option<Instant>,
+    /// The last activity that was emitted.
+    last_activity: Option<Activity>,
+    /// The latest activity that is waiting for the interval to elapse.
+    pending: Option<Activity>,
+}
+
+impl Default for Debouncer {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEBOUNCE_INTERVAL)
+    }
+}
+
+impl Debouncer {
+    /// Creates a new `Debouncer`.
+    pub fn new(interval: Duration) -> Self {
+        Debouncer {
+            interval,
+            last_emit: None,
+            last_activity: None,
+            pending: None,
+        }
+    }
+
+    /// Submits an activity, returning it if it should be sent now.
+    ///
+    /// Unchanged activities are dropped. Changes within the interval are held back
+    /// until `poll` is called after the interval has elapsed.
+    pub fn submit(&mut self, activity: Activity) -> Option<Activity> {
+        if self.last_activity.as_ref() == Some(&activity) {
+            self.pending = None;
+            return None;
+        }
+        self.pending = Some(activity);
+        self.poll()
+    }
+
+    /// Returns the pending activity if the interval has elapsed.
+    pub fn poll(&mut self) -> Option<Activity> {
+        let ready = self
+            .last_emit
+            .is_none_or(|last_emit| last_emit.elapsed() >= self.interval);
+        if !ready {
+            return None;
+        }
+
+        let activity = self.pending.take()?;
+        self.last_emit = Some(Instant::now());
+        self.last_activity = Some(activity.clone());
+        Some(activity)
+    }
+}
+
+/// Computes the timestamps for a playback position, in Unix milliseconds.
+fn progress_timestamps(
+    position: Option<Duration>,
+    duration: Option<Duration>,
+) -> Option<Timestamps> {
+    match (position, duration) {
+        (position, Some(duration)) => Some(Timestamps::for_progress(
+            position.unwrap_or_default(),
+            duration,
+        )),
+        (Some(position), None) => Some(Timestamps::for_elapsed(position)),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_timestamps_follow_position_and_duration() {
+        assert!(progress_timestamps(None, None).is_none());
+
+        let elapsed = progress_timestamps(Some(Duration::from_secs(10)), None).unwrap();
+        assert!(elapsed.start().is_some());
+        assert_eq!(elapsed.end(), None);
+
+        let progress =
+            progress_timestamps(Some(Duration::from_secs(10)), Some(Duration::from_secs(60)))
+                .unwrap();
+        assert_eq!(progress.end(), Some(progress.start().unwrap() + 60_000));
+
+        // Without a position, the bar starts empty.
+        let empty = progress_timestamps(None, Some(Duration::from_secs(60))).unwrap();
+        let full =
+            progress_timestamps(Some(Duration::from_secs(90)), Some(Duration::from_secs(60)))
+                .unwrap();
+        let start = empty.start().unwrap();
+        assert!((start - 60_000..start - 59_000).contains(&full.start().unwrap()));
+    }
+
+    #[test]
+    fn track_presence_builds_a_listening_activity() {
+        let activity = TrackPresence::new("Song".to_string())
+            .set_artist("Band".to_string())
+            .set_album("Album".to_string())
+            .set_position(Duration::from_secs(30))
+            .set_duration(Duration::from_secs(200))
+            .to_activity();
+
+        assert_eq!(activity.activity_type(), Some(&ActivityType::Listening));
+        assert_eq!(activity.details(), Some("Song"));
+        assert_eq!(activity.state(), Some("by Band"));
+        let timestamps = activity.timestamps().unwrap();
+        assert_eq!(
+            timestamps.end(),
+            Some(timestamps.start().unwrap() + 200_000)
+        );
+    }
+
+    #[test]
+    fn paused_watching_presence_omits_the_timestamps() {
+        let video = WatchingPresence::new("Show".to_string())
+            .set_season(1)
+            .set_episode(2)
+            .set_position(Duration::from_secs(60))
+            .set_duration(Duration::from_secs(1200));
+
+        let playing = video.clone().to_activity();
+        assert_eq!(playing.state(), Some("S1E2"));
+        assert!(playing.timestamps().is_some());
+
+        let paused = video.set_paused(true).to_activity();
+        assert_eq!(paused.activity_type(), Some(&ActivityType::Watching));
+        assert_eq!(paused.state(), Some("S1E2 (Paused)"));
+        assert!(paused.timestamps().is_none());
+
+        let paused = WatchingPresence::new("Movie".to_string())
+            .set_paused(true)
+            .to_activity();
+        assert_eq!(paused.state(), Some("Paused"));
+    }
+}
@@ -0,0 +1,497 @@
+use crate::activities::{MAX_BUTTON_LABEL_LEN, MAX_BUTTONS, MAX_TEXT_LEN};
+use crate::error::Error;
+use serde_json::Value;
+
+/// Describes the expected shape of a JSON value in an activity.
+enum Shape {
+    /// A string of at most the given number of characters, if limited.
+    String(Option<usize>),
+    /// A non-negative integer no larger than the given maximum.
+    Integer(u64),
+    /// A boolean.
+    Bool,
+    /// An object with the given optional fields.
+    Object(&'static [(&'static str, Shape)]),
+    /// The current and maximum size of a party.
+    PartySize,
+    /// The list of buttons.
+    Buttons,
+}
+
+/// The fields of an activity known to this crate.
+const ACTIVITY: &[(&str, Shape)] = &[
+    ("details", Shape::String(Some(MAX_TEXT_LEN))),
+    ("details_url", Shape::String(None)),
+    ("state", Shape::String(Some(MAX_TEXT_LEN))),
+    ("state_url", Shape::String(None)),
+    ("assets", Shape::Object(ASSETS)),
+    ("timestamps", Shape::Object(TIMESTAMPS)),
+    ("party", Shape::Object(PARTY)),
+    ("secrets", Shape::Object(SECRETS)),
+    ("buttons", Shape::Buttons),
+    ("type", Shape::Integer(5)),
+    ("status_display_type", Shape::Integer(2)),
+];
+
+/// The fields of the assets of an activity.
+const ASSETS: &[(&str, Shape)] = &[
+    ("large_image", Shape::String(None)),
+    ("large_text", Shape::String(Some(MAX_TEXT_LEN))),
+    ("large_url", Shape::String(None)),
+    ("small_image", Shape::String(None)),
+    ("small_text", Shape::String(Some(MAX_TEXT_LEN))),
+    ("small_url", Shape::String(None)),
+];
+
+/// The fields of the timestamps of an activity.
+const TIMESTAMPS: &[(&str, Shape)] = &[
+    ("start", Shape::Integer(u64::MAX)),
+    ("end", Shape::Integer(u64::MAX)),
+];
+
+/// The fields of the party of an activity.
+const PARTY: &[(&str, Shape)] = &[("id", Shape::String(None)), ("size", Shape::PartySize)];
+
+/// The fields of the secrets of an activity.
+const SECRETS: &[(&str, Shape)] = &[
+    ("join", Shape::String(None)),
+    ("spectate", Shape::String(None)),
+    ("instance", Shape::Bool),
+    ("match", Shape::String(None)),
+];
+
+/// Represents a part of a raw activity that does not match the SET_ACTIVITY schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// The path of the offending value, such as `$.assets.large_text`.
+    pub path: String,
+    /// What is wrong with the value.
+    pub kind: ViolationKind,
+}
+
+/// Represents what is wrong with a value in a raw activity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The field is not part of the schema.
+    UnknownField,
+    /// A required field is missing or empty.
+    Missing,
+    /// The value has the wrong JSON type.
+    WrongType {
+        /// The JSON type that was expected.
+        expected: &'static str,
+    },
+    /// A string is longer than Discord allows.
+    TooLong {
+        /// The length of the string, in characters.
+        len: usize,
+        /// The maximum length allowed, in characters.
+        max: usize,
+    },
+    /// A list has more entries than Discord allows.
+    TooMany {
+        /// The number of entries in the list.
+        count: usize,
+        /// The maximum number of entries allowed.
+        max: usize,
+    },
+    /// An integer is larger than Discord allows.
+    OutOfRange {
+        /// The maximum value allowed.
+        max: u64,
+    },
+    /// A button URL does not use the `https` or `discord` scheme.
+    InvalidUrl,
+    /// Buttons are combined with a join or spectate secret.
+    ButtonsWithSecrets,
+    /// Secrets are set without a party ID.
+    SecretsWithoutParty,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.path)?;
+        match &self.kind {
+            ViolationKind::UnknownField => f.write_str("unknown field"),
+            ViolationKind::Missing => f.write_str("missing"),
+            ViolationKind::WrongType { expected } => write!(f, "expected {}", expected),
+            ViolationKind::TooLong { len, max } => {
+                write!(f, "too long ({} characters, max {})", len, max)
+            }
+            ViolationKind::TooMany { count, max } => {
+                write!(f, "too many entries ({}, max {})", count, max)
+            }
+            ViolationKind::OutOfRange { max } => write!(f, "out of range (max {})", max),
+            ViolationKind::InvalidUrl => f.write_str("URL must use https or discord"),
+            ViolationKind::ButtonsWithSecrets => {
+                f.write_str("cannot be combined with join or spectate secrets")
+            }
+            ViolationKind::SecretsWithoutParty => f.write_str("require a party ID"),
+        }
+    }
+}
+
+/// Checks a raw activity against the SET_ACTIVITY schema known to this crate.
+///
+/// Every field name, type and limit is checked, as well as the combinations that
+/// `Activity::validate` rejects, and all violations are reported at once with
+/// `Error::InvalidActivityJson`. A known field set to `null` is treated as missing.
+/// Fields added by Discord after this crate are reported as unknown.
+pub fn validate_activity(activity: &Value) -> Result<(), Error> {
+    let mut violations = Vec::new();
+    check(activity, &Shape::Object(ACTIVITY), "$", &mut violations);
+    if activity.is_object() {
+        check_combinations(activity, &mut violations);
+    }
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidActivityJson(violations))
+    }
+}
+
+/// Checks `value` against `shape`, collecting violations under `path`.
+fn check(value: &Value, shape: &Shape, path: &str, violations: &mut Vec<Violation>) {
+    let mut report = |path: &str, kind| {
+        violations.push(Violation {
+            path: path.to_string(),
+            kind,
+        })
+    };
+
+    match shape {
+        Shape::String(max) => match value.as_str() {
+            Some(text) => {
+                let len = text.chars().count();
+                if let Some(max) = *max
+                    && len > max
+                {
+                    report(path, ViolationKind::TooLong { len, max });
+                }
+            }
+            None => report(
+                path,
+                ViolationKind::WrongType {
+                    expected: "a string",
+                },
+            ),
+        },
+        Shape::Integer(max) => match value.as_u64() {
+            Some(number) if number > *max => report(path, ViolationKind::OutOfRange { max: *max }),
+            Some(_) => {}
+            None => report(
+                path,
+                ViolationKind::WrongType {
+                    expected: "a non-negative integer",
+                },
+            ),
+        },
+        Shape::Bool => {
+            if !value.is_boolean() {
+                report(
+                    path,
+                    ViolationKind::WrongType {
+                        expected: "a boolean",
+                    },
+                );
+            }
+        }
+        Shape::Object(fields) => {
+            let Some(object) = value.as_object() else {
+                report(
+                    path,
+                    ViolationKind::WrongType {
+                        expected: "an object",
+                    },
+                );
+                return;
+            };
+            for (name, value) in object {
+                let path = format!("{}.{}", path, name);
+                match fields.iter().find(|(field, _)| field == name) {
+                    Some(_) if value.is_null() => {}
+                    Some((_, shape)) => check(value, shape, &path, violations),
+                    None => violations.push(Violation {
+                        path,
+                        kind: ViolationKind::UnknownField,
+                    }),
+                }
+            }
+        }
+        Shape::PartySize => match value.as_array() {
+            Some(size) if size.len() == 2 => {
+                for (i, value) in size.iter().enumerate() {
+                    let path = format!("{}[{}]", path, i);
+                    check(
+                        value,
+                        &Shape::Integer(u64::from(u32::MAX)),
+                        &path,
+                        violations,
+                    );
+                }
+            }
+            _ => report(
+                path,
+                ViolationKind::WrongType {
+                    expected: "an array of two integers",
+                },
+            ),
+        },
+        Shape::Buttons => {
+            let Some(buttons) = value.as_array() else {
+                report(
+                    path,
+                    ViolationKind::WrongType {
+                        expected: "an array",
+                    },
+                );
+                return;
+            };
+            if buttons.len() > MAX_BUTTONS {
+                report(
+                    path,
+                    ViolationKind::TooMany {
+                        count: buttons.len(),
+                        max: MAX_BUTTONS,
+                    },
+                );
+            }
+            for (i, button) in buttons.iter().enumerate() {
+                check_button(button, &format!("{}[{}]", path, i), violations);
+            }
+        }
+    }
+}
+
+/// Checks the fields of an activity that depend on each other.
+fn check_combinations(activity: &Value, violations: &mut Vec<Violation>) {
+    let secrets = &activity["secrets"];
+    let has_secret = |name: &str| !secrets[name].is_null();
+    let has_buttons = activity["buttons"]
+        .as_array()
+        .is_some_and(|buttons| !buttons.is_empty());
+
+    if has_buttons && (has_secret("join") || has_secret("spectate")) {
+        violations.push(Violation {
+            path: "$.buttons".to_string(),
+            kind: ViolationKind::ButtonsWithSecrets,
+        });
+    }
+    let has_any_secret = has_secret("join") || has_secret("spectate") || has_secret("match");
+    if has_any_secret && activity["party"]["id"].is_null() {
+        violations.push(Violation {
+            path: "$.secrets".to_string(),
+            kind: ViolationKind::SecretsWithoutParty,
+        });
+    }
+}
+
+/// Checks a single button, collecting violations under `path`.
+fn check_button(button: &Value, path: &str, violations: &mut Vec<Violation>) {
+    const BUTTON: &[(&str, Shape)] = &[
+        ("label", Shape::String(Some(MAX_BUTTON_LABEL_LEN))),
+        ("url", Shape::String(None)),
+    ];
+
+    let before = violations.len();
+    check(button, &Shape::Object(BUTTON), path, violations);
+    if violations.len() > before || !button.is_object() {
+        return;
+    }
+
+    for field in ["label", "url"] {
+        if button[field].as_str().is_none_or(str::is_empty) {
+            violations.push(Violation {
+                path: format!("{}.{}", path, field),
+                kind: ViolationKind::Missing,
+            });
+        }
+    }
+    if let Some(url) = button["url"].as_str().filter(|url| !url.is_empty()) {
+        let scheme = url.split_once("://").map(|(scheme, _)| scheme);
+        if !matches!(
+            scheme.map(str::to_ascii_lowercase).as_deref(),
+            Some("https") | Some("discord")
+        ) {
+            violations.push(Violation {
+                path: format!("{}.url", path),
+                kind: ViolationKind::InvalidUrl,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Returns the violations reported for `activity`.
+    fn violations(activity: Value) -> Vec<Violation> {
+        match validate_activity(&activity) {
+            Ok(()) => Vec::new(),
+            Err(Error::InvalidActivityJson(violations)) => violations,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn accepts_a_valid_activity() {
+        let activity = json!({
+            "details": "Playing",
+            "state": "In a match",
+            "assets": { "large_image": "logo", "large_text": "Logo" },
+            "timestamps": { "start": 1_700_000_000 },
+            "party": { "id": "party", "size": [1, 4] },
+            "buttons": [{ "label": "Website", "url": "https://example.com" }],
+            "type": 0,
+        });
+        assert_eq!(violations(activity), Vec::new());
+    }
+
+    #[test]
+    fn reports_every_violation_with_its_path() {
+        let activity = json!({
+            "details": "a".repeat(MAX_TEXT_LEN + 1),
+            "assets": { "large_image": 5, "extra": true },
+            "party": { "size": [1] },
+            "type": 6,
+            "unknown": null,
+        });
+        let mut found = violations(activity);
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            found,
+            vec![
+                Violation {
+                    path: "$.assets.extra".to_string(),
+                    kind: ViolationKind::UnknownField,
+                },
+                Violation {
+                    path: "$.assets.large_image".to_string(),
+                    kind: ViolationKind::WrongType {
+                        expected: "a string"
+                    },
+                },
+                Violation {
+                    path: "$.details".to_string(),
+                    kind: ViolationKind::TooLong {
+                        len: MAX_TEXT_LEN + 1,
+                        max: MAX_TEXT_LEN
+                    },
+                },
+                Violation {
+                    path: "$.party.size".to_string(),
+                    kind: ViolationKind::WrongType {
+                        expected: "an array of two integers"
+                    },
+                },
+                Violation {
+                    path: "$.type".to_string(),
+                    kind: ViolationKind::OutOfRange { max: 5 },
+                },
+                Violation {
+                    path: "$.unknown".to_string(),
+                    kind: ViolationKind::UnknownField,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_buttons() {
+        let activity = json!({
+            "buttons": [
+                { "label": "", "url": "https://example.com" },
+                { "label": "Scheme", "url": "http://example.com" },
+                { "label": "Ok", "url": "discord://-/library" },
+            ],
+        });
+        assert_eq!(
+            violations(activity),
+            vec![
+                Violation {
+                    path: "$.buttons".to_string(),
+                    kind: ViolationKind::TooMany {
+                        count: 3,
+                        max: MAX_BUTTONS
+                    },
+                },
+                Violation {
+                    path: "$.buttons[0].label".to_string(),
+                    kind: ViolationKind::Missing,
+                },
+                Violation {
+                    path: "$.buttons[1].url".to_string(),
+                    kind: ViolationKind::InvalidUrl,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_a_non_object() {
+        assert_eq!(
+            violations(json!([])),
+            vec![Violation {
+                path: "$".to_string(),
+                kind: ViolationKind::WrongType {
+                    expected: "an object"
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn checks_combinations_like_activity_validate() {
+        let button = json!([{ "label": "Website", "url": "https://example.com" }]);
+        let activity = json!({
+            "buttons": button,
+            "secrets": { "join": "secret" },
+        });
+        assert_eq!(
+            violations(activity),
+            vec![
+                Violation {
+                    path: "$.buttons".to_string(),
+                    kind: ViolationKind::ButtonsWithSecrets,
+                },
+                Violation {
+                    path: "$.secrets".to_string(),
+                    kind: ViolationKind::SecretsWithoutParty,
+                },
+            ]
+        );
+
+        let activity = json!({
+            "buttons": button,
+            "party": { "id": "party" },
+            "secrets": { "match": "secret" },
+        });
+        assert_eq!(violations(activity), Vec::new());
+    }
+
+    #[test]
+    fn treats_null_as_missing() {
+        let activity = json!({
+            "details": null,
+            "assets": { "large_image": null },
+            "party": null,
+            "secrets": { "join": null },
+            "buttons": null,
+        });
+        assert_eq!(violations(activity), Vec::new());
+
+        let activity = json!({
+            "party": { "id": null },
+            "secrets": { "spectate": "secret" },
+        });
+        assert_eq!(
+            violations(activity),
+            vec![Violation {
+                path: "$.secrets".to_string(),
+                kind: ViolationKind::SecretsWithoutParty,
+            }]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::discord_connection::Client;
+use crate::error::Error;
+use std::sync::Arc;
+#[cfg(any(unix, windows))]
+use std::time::Duration;
+
+/// How long the handlers wait for the activity to be cleared before going on.
+#[cfg(any(unix, windows))]
+const CLEANUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Installs handlers that clear the activity and close the connection on termination.
+///
+/// Handles SIGINT, SIGTERM and SIGHUP on Unix, and the console control events
+/// (Ctrl-C, Ctrl-Break and console close) on Windows. After cleaning up, the process
+/// terminates the way it would have without the handlers: on Unix, the signal is
+/// raised again with its default disposition, so the exit status reports the signal
+/// that was received.
+///
+/// Cleanup is given two seconds, so a hung Discord or a lock held by another thread
+/// cannot keep the process alive. On Unix, the default dispositions are restored
+/// before cleanup starts, so a second signal terminates the process right away.
+///
+/// Only one set of handlers can be installed per process; a second call returns an error.
+pub fn install_signal_handlers(client: Arc<Client>) -> Result<(), Error> {
+    install(client, None)
+}
+
+/// Installs handlers that clear the activity and close the connection on termination,
+/// then call `on_signal` instead of terminating the process.
+///
+/// This lets the application run its own shutdown, such as flushing files, and decide
+/// when to exit. The handlers stay installed, so `on_signal` is called again for every
+/// later signal. Cleanup is given two seconds before `on_signal` is called anyway. On
+/// Windows, the system still ends the process shortly after the console is closed.
+///
+/// Only one set of handlers can be installed per process; a second call returns an error.
+pub fn install_signal_handlers_with<F>(client: Arc<Client>, on_signal: F) -> Result<(), Error>
+where
+    F: Fn() + Send + 'static,
+{
+    install(client, Some(Box::new(on_signal)))
+}
+
+/// Clears the activity and closes the connection of `client`, waiting at most
+/// `CLEANUP_TIMEOUT`.
+///
+/// Cleanup runs on its own thread, which is left behind if it does not finish in time.
+#[cfg(any(unix, windows))]
+fn clean_up(client: &Arc<Client>) {
+    let client = Arc::clone(client);
+    let (done, finished) = std::sync::mpsc::channel();
+    let spawned = std::thread::Builder::new()
+        .name("discord-presence-cleanup".to_string())
+        .spawn(move || {
+            let _ = client.clear_activity();
+            let _ = client.close();
+            let _ = done.send(());
+        });
+    if spawned.is_ok() {
+        let _ = finished.recv_timeout(CLEANUP_TIMEOUT);
+    }
+}
+
+/// Installs the handlers, terminating the process after cleaning up unless `on_signal`
+/// is given.
+#[cfg(unix)]
+fn install(client: Arc<Client>, on_signal: Option<Box<dyn Fn() + Send>>) -> Result<(), Error> {
+    let read_fd = unix::install()?;
+    std::thread::Builder::new()
+        .name("discord-presence-signals".to_string())
+        .spawn(move || {
+            while let Some(signal) = unix::wait(read_fd) {
+                match &on_signal {
+                    Some(on_signal) => {
+                        clean_up(&client);
+                        on_signal();
+                    }
+                    None => {
+                        // Let a second signal end the process if cleanup hangs.
+                        unix::restore_defaults();
+                        clean_up(&client);
+                        unix::raise_default(signal);
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Installs the handlers, terminating the process after cleaning up unless `on_signal`
+/// is given.
+#[cfg(windows)]
+fn install(client: Arc<Client>, on_signal: Option<Box<dyn Fn() + Send>>) -> Result<(), Error> {
+    /// The exit status of a console process ended by Ctrl-C, `STATUS_CONTROL_C_EXIT`.
+    const STATUS_CONTROL_C_EXIT: i32 = 0xC000_013A_u32 as i32;
+
+    ctrlc::set_handler(move || {
+        clean_up(&client);
+        match &on_signal {
+            Some(on_signal) => on_signal(),
+            None => std::process::exit(STATUS_CONTROL_C_EXIT),
+        }
+    })?;
+    Ok(())
+}
+
+/// Returns `Error::UnsupportedPlatform`, since the platform has no termination signals.
+#[cfg(not(any(unix, windows)))]
+fn install(client: Arc<Client>, on_signal: Option<Box<dyn Fn() + Send>>) -> Result<(), Error> {
+    let _ = (client, on_signal);
+    Err(Error::UnsupportedPlatform)
+}
+
+/// Self-pipe based signal handling, so that cleanup runs outside of the signal handler.
+#[cfg(unix)]
+mod unix {
+    use std::os::raw::c_int;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// The signals that are handled.
+    const SIGNALS: [c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    /// The write end of the self-pipe, or `-1` before the handlers are installed.
+    static PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+    /// Writes the received signal to the self-pipe.
+    extern "C" fn on_signal(signal: c_int) {
+        let fd = PIPE_WRITE.load(Ordering::Relaxed);
+        let byte = signal as u8;
+        // SAFETY: `write` is async-signal-safe and `byte` is valid for one byte.
+        unsafe {
+            libc::write(fd, (&raw const byte).cast(), 1);
+        }
+    }
+
+    /// Creates the self-pipe and installs the signal handlers.
+    ///
+    /// Returns the read end of the pipe.
+    pub(super) fn install() -> std::io::Result<c_int> {
+        let mut fds = [0 as c_int; 2];
+        // SAFETY: `fds` is valid for writes of two descriptors.
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        for fd in fds {
+            // SAFETY: `fd` was just opened by `pipe`.
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        // Keep a full pipe from blocking the signal handler.
+        // SAFETY: `fds[1]` was just opened by `pipe`.
+        unsafe {
+            libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK);
+        }
+
+        if PIPE_WRITE
+            .compare_exchange(-1, fds[1], Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            close(fds);
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "signal handlers are already installed",
+            ));
+        }
+
+        // SAFETY: A zeroed `sigaction` is valid.
+        let mut previous: [libc::sigaction; SIGNALS.len()] = unsafe { std::mem::zeroed() };
+        for (i, signal) in SIGNALS.into_iter().enumerate() {
+            // SAFETY: A zeroed `sigaction` is valid, `on_signal` only calls
+            // async-signal-safe functions and `previous[i]` is valid for writes.
+            let result = unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = on_signal as extern "C" fn(c_int) as libc::sighandler_t;
+                action.sa_flags = libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(signal, &action, &mut previous[i])
+            };
+            if result != 0 {
+                let error = std::io::Error::last_os_error();
+                // Put back the handlers installed so far, then release the pipe.
+                for (signal, previous) in SIGNALS.into_iter().zip(&previous).take(i) {
+                    // SAFETY: `previous` was filled in by a successful `sigaction`.
+                    unsafe {
+                        libc::sigaction(signal, previous, std::ptr::null_mut());
+                    }
+                }
+                PIPE_WRITE.store(-1, Ordering::SeqCst);
+                close(fds);
+                return Err(error);
+            }
+        }
+        Ok(fds[0])
+    }
+
+    /// Closes both ends of the self-pipe.
+    fn close(fds: [c_int; 2]) {
+        for fd in fds {
+            // SAFETY: `fd` was opened by `pipe` and is not used elsewhere.
+            unsafe {
+                libc::close(fd);
+            }
+        }
+    }
+
+    /// Waits for the next signal written to the self-pipe.
+    ///
+    /// Returns `None` if the pipe can no longer be read.
+    pub(super) fn wait(read_fd: c_int) -> Option<c_int> {
+        let mut byte = 0u8;
+        loop {
+            // SAFETY: `byte` is valid for writes of one byte.
+            let read = unsafe { libc::read(read_fd, (&raw mut byte).cast(), 1) };
+            if read == 1 {
+                return Some(c_int::from(byte));
+            }
+            if read == -1
+                && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+            {
+                continue;
+            }
+            return None;
+        }
+    }
+
+    /// Restores the default disposition of every handled signal.
+    pub(super) fn restore_defaults() {
+        for signal in SIGNALS {
+            // SAFETY: Restoring the default disposition has no preconditions.
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+        }
+    }
+
+    /// Raises `signal` again with its default disposition, which ends the process.
+    pub(super) fn raise_default(signal: c_int) {
+        // SAFETY: Restoring the default disposition and raising a signal have no
+        // preconditions.
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use crate::error::Error;
+use crate::frame::{DEFAULT_MAX_FRAME_SIZE, Frame, Opcode};
+use crate::platform::{Discovery, DiscoverySource};
+use serde_json::{Value, json};
+#[cfg(windows)]
+use std::fs::File as Connection;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream as Connection};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// A stand-in for the Discord IPC server, for end-to-end tests.
+///
+/// The server listens on a real Unix socket, or on a named pipe such as
+/// `\\.\pipe\discord-presence-test` on Windows. It answers handshakes with a configurable
+/// READY payload, acknowledges commands the way Discord does, and records every frame
+/// it receives. Point a client at it with `discovery`.
+///
+/// The socket file is removed when the server is dropped. On Windows, the client
+/// cannot poll for events (see `PipeOptions`), so only the request and response side
+/// of the protocol can be exercised there.
+pub struct FakeDiscordServer {
+    path: PathBuf,
+    shared: Arc<Shared>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+/// The state shared with the connection threads.
+struct Shared {
+    /// The data sent in the READY event.
+    ready: Mutex<Value>,
+    /// The code and message that handshakes are rejected with, if any.
+    handshake_error: Mutex<Option<(i64, String)>>,
+    /// Every frame received, in order.
+    received: Mutex<Vec<Frame>>,
+    /// The open connections. Frames are only written while this lock is held.
+    connections: Mutex<Vec<Connection>>,
+    stopping: AtomicBool,
+}
+
+impl FakeDiscordServer {
+    /// Binds the server to a Unix socket, or a named pipe on Windows, at `path` and
+    /// starts accepting connections.
+    ///
+    /// Fails if another server already listens at `path`.
+    pub fn bind(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        #[cfg(unix)]
+        let listener = UnixListener::bind(&path)?;
+        #[cfg(windows)]
+        let listener = windows::create_instance(&path, true)?;
+        let shared = Arc::new(Shared {
+            ready: Mutex::new(default_ready()),
+            handshake_error: Mutex::new(None),
+            received: Mutex::new(Vec::new()),
+            connections: Mutex::new(Vec::new()),
+            stopping: AtomicBool::new(false),
+        });
+
+        let accept_shared = Arc::clone(&shared);
+        #[cfg(unix)]
+        let accept_thread = std::thread::spawn(move || accept(listener, accept_shared));
+        #[cfg(windows)]
+        let accept_path = path.clone();
+        #[cfg(windows)]
+        let accept_thread =
+            std::thread::spawn(move || windows::accept(listener, accept_path, accept_shared));
+
+        Ok(FakeDiscordServer {
+            path,
+            shared,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    /// Returns the path of the socket or pipe.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a `Discovery` that only tries the socket or pipe of this server.
+    pub fn discovery(&self) -> Discovery {
+        Discovery::new()
+            .add_path(self.path.clone())
+            .set_order(vec![DiscoverySource::Paths])
+    }
+
+    /// Sets the data sent in the READY event of later handshakes.
+    pub fn set_ready(&self, ready: Value) {
+        *lock(&self.shared.ready) = ready;
+    }
+
+    /// Rejects later handshakes with a close frame carrying `code` and `message`.
+    pub fn fail_handshakes(&self, code: i64, message: &str) {
+        *lock(&self.shared.handshake_error) = Some((code, message.to_string()));
+    }
+
+    /// Accepts later handshakes again.
+    pub fn accept_handshakes(&self) {
+        *lock(&self.shared.handshake_error) = None;
+    }
+
+    /// Returns every frame received so far, in order.
+    pub fn received(&self) -> Vec<Frame> {
+        lock(&self.shared.received).clone()
+    }
+
+    /// Returns the JSON payloads of the commands received so far, in order.
+    pub fn received_commands(&self) -> Vec<Value> {
+        lock(&self.shared.received)
+            .iter()
+            .filter(|frame| frame.opcode == Opcode::Frame)
+            .filter_map(|frame| serde_json::from_slice(&frame.payload).ok())
+            .collect()
+    }
+
+    /// Sends `frame` to every connected client.
+    ///
+    /// Connections that can no longer be written to are dropped.
+    pub fn inject(&self, frame: &Frame) {
+        lock(&self.shared.connections).retain_mut(|connection| frame.write_to(connection).is_ok());
+    }
+
+    /// Sends a DISPATCH frame for the event `evt` to every connected client.
+    pub fn inject_event(&self, evt: &str, data: Value) {
+        let payload = json!({
+            "cmd": "DISPATCH",
+            "evt": evt,
+            "data": data,
+            "nonce": null
+        });
+        self.inject(&Frame::new(Opcode::Frame, payload.to_string().into_bytes()))
+    }
+
+    /// Sends an ERROR event with `code` and `message` to every connected client.
+    pub fn inject_error(&self, code: i64, message: &str) {
+        self.inject_event("ERROR", json!({ "code": code, "message": message }))
+    }
+
+    /// Sends a close frame to every connected client and disconnects them.
+    pub fn close_connections(&self, code: i64, message: &str) {
+        let frame = close_frame(code, message);
+        for mut connection in lock(&self.shared.connections).drain(..) {
+            let _ = frame.write_to(&mut connection);
+            disconnect(&connection);
+        }
+    }
+}
+
+impl Drop for FakeDiscordServer {
+    fn drop(&mut self) {
+        self.shared.stopping.store(true, Ordering::SeqCst);
+        for connection in lock(&self.shared.connections).drain(..) {
+            disconnect(&connection);
+        }
+
+        // Wake the accept loop so it sees the stop flag.
+        #[cfg(unix)]
+        let _ = Connection::connect(&self.path);
+        #[cfg(windows)]
+        let _ = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.path);
+        if let Some(accept_thread) = self.accept_thread.take() {
+            let _ = accept_thread.join();
+        }
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Accepts connections until the server is stopped.
+#[cfg(unix)]
+fn accept(listener: UnixListener, shared: Arc<Shared>) {
+    for stream in listener.incoming() {
+        if shared.stopping.load(Ordering::SeqCst) {
+            return;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let Ok(writer) = stream.try_clone() else {
+            continue;
+        };
+        lock(&shared.connections).push(writer);
+
+        let shared = Arc::clone(&shared);
+        std::thread::spawn(move || handle_connection(stream, &shared));
+    }
+}
+
+/// Answers every frame sent over a connection until it is closed.
+#[cfg(unix)]
+fn handle_connection(mut stream: Connection, shared: &Shared) {
+    while let Ok(frame) = Frame::read_from(&mut stream, DEFAULT_MAX_FRAME_SIZE) {
+        if !answer(frame, &mut stream, shared) {
+            break;
+        }
+    }
+    disconnect(&stream);
+}
+
+/// Records `frame` and writes the response to it.
+///
+/// Returns `false` once the connection should be closed.
+fn answer(frame: Frame, stream: &mut Connection, shared: &Shared) -> bool {
+    lock(&shared.received).push(frame.clone());
+
+    let response = match frame.opcode {
+        Opcode::Handshake => match lock(&shared.handshake_error).clone() {
+            Some((code, message)) => close_frame(code, &message),
+            None => {
+                let payload = json!({
+                    "cmd": "DISPATCH",
+                    "evt": "READY",
+                    "data": lock(&shared.ready).clone(),
+                    "nonce": null
+                });
+                Frame::new(Opcode::Frame, payload.to_string().into_bytes())
+            }
+        },
+        Opcode::Frame => command_response(&frame),
+        Opcode::Close => close_frame(1000, "Closed by client"),
+        Opcode::Ping => Frame::new(Opcode::Pong, frame.payload),
+        Opcode::Pong => return true,
+    };
+
+    let closing = response.opcode == Opcode::Close;
+    let written = {
+        let _connections = lock(&shared.connections);
+        response.write_to(stream)
+    };
+    written.is_ok() && !closing
+}
+
+/// Closes both directions of a connection, waking any thread reading from it.
+fn disconnect(connection: &Connection) {
+    #[cfg(unix)]
+    let _ = connection.shutdown(std::net::Shutdown::Both);
+    #[cfg(windows)]
+    windows::disconnect(connection);
+}
+
+/// Builds the acknowledgement of a command, echoing its `cmd` and `nonce`.
+fn command_response(frame: &Frame) -> Frame {
+    let command: Value = serde_json::from_slice(&frame.payload).unwrap_or_default();
+    let data = match command["cmd"].as_str() {
+        Some("SET_ACTIVITY") => command["args"]["activity"].clone(),
+        _ => Value::Null,
+    };
+    let payload = json!({
+        "cmd": command["cmd"],
+        "evt": null,
+        "data": data,
+        "nonce": command["nonce"]
+    });
+    Frame::new(Opcode::Frame, payload.to_string().into_bytes())
+}
+
+/// Builds a close frame carrying `code` and `message`.
+fn close_frame(code: i64, message: &str) -> Frame {
+    let payload = json!({ "code": code, "message": message });
+    Frame::new(Opcode::Close, payload.to_string().into_bytes())
+}
+
+/// Returns the READY data sent until `set_ready` is called.
+fn default_ready() -> Value {
+    json!({
+        "v": 1,
+        "config": {
+            "cdn_host": "cdn.discordapp.com",
+            "api_endpoint": "//discord.com/api",
+            "environment": "production"
+        },
+        "user": {
+            "id": "0",
+            "username": "fake-user",
+            "discriminator": "0",
+            "avatar": null
+        }
+    })
+}
+
+/// Locks a mutex, recovering it if another thread panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The named-pipe side of the server.
+///
+/// Pipe handles are opened for synchronous I/O, which the system serializes per handle:
+/// a thread blocked reading would also block `inject` writing from another thread. The
+/// connection threads therefore only read bytes that `PeekNamedPipe` reports as
+/// available.
+#[cfg(windows)]
+mod windows {
+    use super::{Connection, DEFAULT_MAX_FRAME_SIZE, Frame, Shared, answer, lock};
+    use std::io::Read;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use windows_sys::Win32::Foundation::{ERROR_PIPE_CONNECTED, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+    };
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_BYTE,
+        PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        PeekNamedPipe,
+    };
+
+    /// The size of the buffers of each pipe instance.
+    const BUFFER_SIZE: u32 = 64 * 1024;
+
+    /// How long a connection thread sleeps when no bytes are available.
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    /// Creates a pipe instance at `path` that waits for a client.
+    ///
+    /// With `first`, fails if the pipe already exists.
+    pub(super) fn create_instance(path: &Path, first: bool) -> std::io::Result<Connection> {
+        let name: Vec<u16> = path
+            .as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let mut open_mode = PIPE_ACCESS_DUPLEX;
+        if first {
+            open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+        }
+        // SAFETY: `name` is a NUL-terminated wide string that outlives the call, and
+        // a null pointer selects the default security attributes.
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                open_mode,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                std::ptr::null(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(std::io::Error::last_os_error());
+        }
+        // SAFETY: `handle` is a valid handle that nothing else owns.
+        Ok(unsafe { Connection::from_raw_handle(handle) })
+    }
+
+    /// Accepts connections until the server is stopped.
+    ///
+    /// Each instance serves one client, so a new one is created for the next client as
+    /// soon as one connects.
+    pub(super) fn accept(mut instance: Connection, path: PathBuf, shared: Arc<Shared>) {
+        loop {
+            // SAFETY: `instance` owns a valid pipe handle, and a null pointer selects
+            // a blocking wait.
+            let connected =
+                unsafe { ConnectNamedPipe(instance.as_raw_handle(), std::ptr::null_mut()) != 0 }
+                    || std::io::Error::last_os_error().raw_os_error()
+                        == Some(ERROR_PIPE_CONNECTED as i32);
+            if shared.stopping.load(Ordering::SeqCst) {
+                return;
+            }
+            let Ok(next) = create_instance(&path, false) else {
+                return;
+            };
+            let stream = std::mem::replace(&mut instance, next);
+            if !connected {
+                continue;
+            }
+            let Ok(writer) = stream.try_clone() else {
+                continue;
+            };
+            lock(&shared.connections).push(writer);
+
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || handle_connection(stream, &shared));
+        }
+    }
+
+    /// Answers every frame sent over a connection until it is closed.
+    fn handle_connection(mut stream: Connection, shared: &Shared) {
+        let mut buffer = Vec::new();
+        'connection: while let Some(available) = available(&stream) {
+            if available == 0 {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            let start = buffer.len();
+            buffer.resize(start + available, 0);
+            match stream.read(&mut buffer[start..]) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => buffer.truncate(start + read),
+            }
+            while let Ok(Some((frame, length))) = Frame::decode(&buffer, DEFAULT_MAX_FRAME_SIZE) {
+                buffer.drain(..length);
+                if !answer(frame, &mut stream, shared) {
+                    break 'connection;
+                }
+            }
+        }
+        disconnect(&stream);
+    }
+
+    /// Returns how many bytes can be read from `stream` without blocking, or `None`
+    /// once the client is gone.
+    fn available(stream: &Connection) -> Option<usize> {
+        let mut available = 0;
+        // SAFETY: `stream` owns a valid pipe handle, and the null pointers skip the
+        // outputs that are not needed.
+        let peeked = unsafe {
+            PeekNamedPipe(
+                stream.as_raw_handle(),
+                std::ptr::null_mut(),
+                0,
+                std::ptr::null_mut(),
+                &mut available,
+                std::ptr::null_mut(),
+            )
+        };
+        (peeked != 0).then_some(available as usize)
+    }
+
+    /// Disconnects the client of a pipe instance.
+    pub(super) fn disconnect(connection: &Connection) {
+        // SAFETY: `connection` owns a valid pipe handle.
+        unsafe { DisconnectNamedPipe(connection.as_raw_handle()) };
+    }
+}
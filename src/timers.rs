@@ -0,0 +1,200 @@
+use crate::activities::Timestamps;
+use std::time::{Duration, Instant};
+
+/// A stopwatch that can be paused and resumed, for media playback presences.
+///
+/// Discord always counts the elapsed time up from the start timestamp, so a paused
+/// timer cannot be displayed. While paused, `to_timestamps` returns `None`; on resume,
+/// the start is recomputed so the elapsed time continues from where it was paused.
+#[derive(Clone, Debug)]
+pub struct StopwatchTimestamps {
+    /// The time accumulated before the current run.
+    elapsed: Duration,
+    /// When the current run started, if the stopwatch is running.
+    running_since: Option<Instant>,
+    /// The total duration, used to compute the end timestamp.
+    duration: Option<Duration>,
+}
+
+impl Default for StopwatchTimestamps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StopwatchTimestamps {
+    /// Creates a new paused `StopwatchTimestamps` at zero.
+    pub fn new() -> Self {
+        StopwatchTimestamps {
+            elapsed: Duration::ZERO,
+            running_since: None,
+            duration: None,
+        }
+    }
+
+    /// Creates a new running `StopwatchTimestamps` at zero.
+    pub fn started() -> Self {
+        let mut stopwatch = Self::new();
+        stopwatch.resume();
+        stopwatch
+    }
+
+    /// Sets the total duration, so the timestamps include an end time.
+    pub fn set_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Pauses the stopwatch.
+    pub fn pause(&mut self) {
+        if let Some(running_since) = self.running_since.take() {
+            self.elapsed += running_since.elapsed();
+        }
+    }
+
+    /// Resumes the stopwatch.
+    pub fn resume(&mut self) {
+        if self.running_since.is_none() {
+            self.running_since = Some(Instant::now());
+        }
+    }
+
+    /// Moves the stopwatch to `position`, for example after a seek.
+    pub fn seek(&mut self, position: Duration) {
+        self.elapsed = position;
+        if self.running_since.is_some() {
+            self.running_since = Some(Instant::now());
+        }
+    }
+
+    /// Returns whether the stopwatch is paused.
+    pub fn is_paused(&self) -> bool {
+        self.running_since.is_none()
+    }
+
+    /// Returns the elapsed time.
+    pub fn elapsed(&self) -> Duration {
+        let running = self
+            .running_since
+            .map(|running_since| running_since.elapsed())
+            .unwrap_or_default();
+        self.elapsed + running
+    }
+
+    /// Converts the stopwatch into timestamps, in Unix milliseconds.
+    ///
+    /// Returns `None` while paused.
+    pub fn to_timestamps(&self) -> Option<Timestamps> {
+        if self.is_paused() {
+            return None;
+        }
+
+        let timestamps = match self.duration {
+            Some(duration) => Timestamps::for_progress(self.elapsed(), duration),
+            None => Timestamps::for_elapsed(self.elapsed()),
+        };
+        Some(timestamps)
+    }
+}
+
+/// Timestamps anchored to the monotonic clock, for timers that survive wall clock changes.
+///
+/// The anchors are converted to Unix milliseconds only when `to_timestamps` is called,
+/// so an NTP correction or a manual clock change does not skew the elapsed time.
+/// Rebuild the activity from `to_timestamps` after the wall clock jumps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MonotonicTimestamps {
+    /// When the timer started.
+    start: Instant,
+    /// When the timer ends, if it has an end.
+    end: Option<Instant>,
+}
+
+impl MonotonicTimestamps {
+    /// Creates a new `MonotonicTimestamps` starting now.
+    pub fn now() -> Self {
+        Self::since(Instant::now())
+    }
+
+    /// Creates a new `MonotonicTimestamps` starting at `start`.
+    pub fn since(start: Instant) -> Self {
+        MonotonicTimestamps { start, end: None }
+    }
+
+    /// Sets the end of the timer.
+    pub fn set_end(mut self, end: Instant) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Sets the end of the timer to `duration` after its start.
+    pub fn set_duration(mut self, duration: Duration) -> Self {
+        self.end = Some(self.start + duration);
+        self
+    }
+
+    /// Returns when the timer started.
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+
+    /// Returns when the timer ends, if it has an end.
+    pub fn end(&self) -> Option<Instant> {
+        self.end
+    }
+
+    /// Converts the anchors into timestamps against the current wall clock, in Unix
+    /// milliseconds.
+    pub fn to_timestamps(&self) -> Timestamps {
+        let elapsed = self.start.elapsed();
+        match self.end {
+            Some(end) => {
+                Timestamps::for_progress(elapsed, end.saturating_duration_since(self.start))
+            }
+            None => Timestamps::for_elapsed(elapsed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How long the tests let a running stopwatch run.
+    const STEP: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn stopwatch_only_counts_while_running() {
+        let mut stopwatch = StopwatchTimestamps::new();
+        assert!(stopwatch.is_paused());
+        assert!(stopwatch.to_timestamps().is_none());
+
+        stopwatch.resume();
+        std::thread::sleep(STEP);
+        stopwatch.pause();
+        let paused_at = stopwatch.elapsed();
+        assert!(paused_at >= STEP);
+        assert!(stopwatch.to_timestamps().is_none());
+
+        std::thread::sleep(STEP);
+        assert_eq!(stopwatch.elapsed(), paused_at);
+
+        stopwatch.resume();
+        std::thread::sleep(STEP);
+        assert!(stopwatch.elapsed() >= paused_at + STEP);
+        assert!(stopwatch.to_timestamps().unwrap().end().is_none());
+    }
+
+    #[test]
+    fn stopwatch_resumes_from_a_seek() {
+        let mut stopwatch = StopwatchTimestamps::started().set_duration(Duration::from_secs(60));
+        stopwatch.pause();
+        stopwatch.seek(Duration::from_secs(30));
+        assert_eq!(stopwatch.elapsed(), Duration::from_secs(30));
+
+        stopwatch.resume();
+        let timestamps = stopwatch.to_timestamps().unwrap();
+        assert_eq!(timestamps.end(), Some(timestamps.start().unwrap() + 60_000));
+        assert!(stopwatch.elapsed() >= Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::activities::Activity;
+use crate::discord_connection::Client;
+use crate::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The default interval between two checks of the watched file.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The default time a file must stay unchanged before it is applied.
+pub const DEFAULT_WATCH_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Watches a JSON (or, with the `toml` feature, TOML) file containing an activity.
+///
+/// The file is loaded whenever its modification time changes and has settled for the
+/// debounce period. Files ending in `.toml` are parsed as TOML, all others as JSON.
+#[derive(Clone, Debug)]
+pub struct FileWatcher {
+    /// The path of the watched file.
+    path: PathBuf,
+    /// The interval between two checks of the file.
+    poll_interval: Duration,
+    /// The time the file must stay unchanged before it is applied.
+    debounce: Duration,
+    /// The last modification time that was seen.
+    last_modified: Option<SystemTime>,
+    /// When a change was first seen, if it has not been loaded yet.
+    changed_at: Option<Instant>,
+    /// The last activity that was loaded.
+    last_activity: Option<Activity>,
+    /// Whether the file was missing at the last check.
+    missing: bool,
+}
+
+impl FileWatcher {
+    /// Creates a new `FileWatcher`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileWatcher {
+            path: path.into(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            debounce: DEFAULT_WATCH_DEBOUNCE,
+            last_modified: None,
+            changed_at: None,
+            last_activity: None,
+            missing: false,
+        }
+    }
+
+    /// Sets the interval between two checks of the file.
+    pub fn set_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Sets the time the file must stay unchanged before it is applied.
+    pub fn set_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Returns the path of the watched file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Checks the file once, returning the activity if it changed and settled.
+    ///
+    /// A change that fails to load or validate is reported once and then skipped
+    /// until the file changes again. Likewise, a missing file is reported once and
+    /// then skipped until it comes back, when it is loaded again.
+    pub fn poll(&mut self) -> Result<Option<Activity>, Error> {
+        let modified = match std::fs::metadata(&self.path).and_then(|meta| meta.modified()) {
+            Ok(modified) => modified,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let reported = std::mem::replace(&mut self.missing, true);
+                self.last_modified = None;
+                self.changed_at = None;
+                self.last_activity = None;
+                return if reported { Ok(None) } else { Err(e.into()) };
+            }
+            Err(e) => return Err(e.into()),
+        };
+        self.missing = false;
+        if self.last_modified != Some(modified) {
+            self.last_modified = Some(modified);
+            self.changed_at = Some(Instant::now());
+        }
+
+        match self.changed_at {
+            Some(changed_at) if changed_at.elapsed() >= self.debounce => {
+                self.changed_at = None;
+            }
+            _ => return Ok(None),
+        }
+
+        let activity = self.load()?;
+        activity.validate()?;
+        if self.last_activity.as_ref() == Some(&activity) {
+            return Ok(None);
+        }
+        self.last_activity = Some(activity.clone());
+        Ok(Some(activity))
+    }
+
+    /// Applies every change of the file to `client` until `stop` is set.
+    ///
+    /// Errors from loading the file or setting the activity are passed to `on_error`.
+    pub fn run(&mut self, client: &Client, stop: &AtomicBool, mut on_error: impl FnMut(Error)) {
+        while !stop.load(Ordering::Relaxed) {
+            match self.poll() {
+                Ok(Some(activity)) => {
+                    if let Err(e) = client.set_activity(activity) {
+                        on_error(e);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => on_error(e),
+            }
+            std::thread::sleep(self.poll_interval);
+        }
+    }
+
+    /// Loads and parses the activity from the file.
+    fn load(&self) -> Result<Activity, Error> {
+        let contents = std::fs::read_to_string(&self.path)?;
+        let is_toml = self
+            .path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+
+        if is_toml {
+            #[cfg(feature = "toml")]
+            return Ok(toml::from_str(&contents)?);
+            #[cfg(not(feature = "toml"))]
+            return Err(Error::UnsupportedFileFormat(self.path.clone()));
+        }
+        Activity::from_json_str(&contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_missing_file_is_reported_once_until_it_comes_back() {
+        let path = std::env::temp_dir().join(format!(
+            "discord-presence-watch-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let mut watcher = FileWatcher::new(&path).set_debounce(Duration::ZERO);
+
+        assert!(matches!(watcher.poll(), Err(Error::Io(_))));
+        assert!(matches!(watcher.poll(), Ok(None)));
+
+        std::fs::write(&path, r#"{"details": "Back"}"#).unwrap();
+        let activity = watcher.poll().unwrap().unwrap();
+        assert_eq!(activity.details(), Some("Back"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(watcher.poll(), Err(Error::Io(_))));
+        assert!(matches!(watcher.poll(), Ok(None)));
+    }
+}
@@ -0,0 +1,276 @@
+//! Drives a `Client` against `FakeDiscordServer` over a real Unix socket, or a named
+//! pipe on Windows.
+//!
+//! The Windows client cannot poll for events or ping, so tests that do are Unix-only.
+#![cfg(any(unix, windows))]
+
+use discord_presence_rs::activities::Activity;
+use discord_presence_rs::discord_connection::Client;
+use discord_presence_rs::error::Error;
+#[cfg(unix)]
+use discord_presence_rs::frame::{Frame, Opcode};
+use discord_presence_rs::test_server::FakeDiscordServer;
+use serde_json::Value;
+#[cfg(unix)]
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+const CLIENT_ID: &str = "1234567890123456789";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Binds a fake server to a socket unique to this process and `test`.
+#[cfg(unix)]
+fn server(test: &str) -> FakeDiscordServer {
+    let path = std::env::temp_dir().join(format!(
+        "discord-presence-test-{}-{}.sock",
+        std::process::id(),
+        test
+    ));
+    let _ = std::fs::remove_file(&path);
+    FakeDiscordServer::bind(path).unwrap()
+}
+
+/// Binds a fake server to a pipe unique to this process and `test`.
+#[cfg(windows)]
+fn server(test: &str) -> FakeDiscordServer {
+    let path = format!(
+        r"\\.\pipe\discord-presence-test-{}-{}",
+        std::process::id(),
+        test
+    );
+    FakeDiscordServer::bind(path).unwrap()
+}
+
+/// Connects a client to `server`.
+fn client(server: &FakeDiscordServer) -> Client {
+    Client::builder(CLIENT_ID)
+        .set_discovery(server.discovery())
+        .build()
+        .unwrap()
+}
+
+/// Polls `client` until the collected messages satisfy `done`, and returns them.
+#[cfg(unix)]
+fn poll_until(client: &Client, done: impl Fn(&[Value]) -> bool) -> Vec<Value> {
+    let deadline = Instant::now() + TIMEOUT;
+    let mut messages = Vec::new();
+    while !done(&messages) {
+        assert!(Instant::now() < deadline, "timed out, got {:?}", messages);
+        messages.extend(client.poll_events().unwrap());
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    messages
+}
+
+/// Returns the messages dispatched for the event `evt`.
+#[cfg(unix)]
+fn events<'a>(messages: &'a [Value], evt: &'a str) -> impl Iterator<Item = &'a Value> {
+    messages.iter().filter(move |message| message["evt"] == evt)
+}
+
+/// Waits until `server` has received at least `count` SET_ACTIVITY commands, and
+/// returns their activities.
+fn activities(server: &FakeDiscordServer, count: usize) -> Vec<Value> {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let activities: Vec<Value> = server
+            .received_commands()
+            .into_iter()
+            .filter(|command| command["cmd"] == "SET_ACTIVITY")
+            .map(|command| command["args"]["activity"].clone())
+            .collect();
+        if activities.len() >= count {
+            return activities;
+        }
+        assert!(Instant::now() < deadline, "timed out, got {:?}", activities);
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn full_session() {
+    let server = server("full_session");
+    let client = client(&server);
+    assert_eq!(client.ready().user.unwrap().username, "fake-user");
+
+    client
+        .set_activity(Activity::new().set_details("Testing".to_string()))
+        .unwrap();
+    assert_eq!(activities(&server, 1)[0]["details"], "Testing");
+
+    server.inject_event("ACTIVITY_JOIN", json!({ "secret": "join" }));
+    let messages = poll_until(&client, |messages| {
+        events(messages, "ACTIVITY_JOIN").next().is_some()
+    });
+    assert!(
+        messages
+            .iter()
+            .any(|message| message["cmd"] == "SET_ACTIVITY")
+    );
+
+    assert!(client.ping(TIMEOUT).unwrap() < TIMEOUT);
+
+    client.close_gracefully(TIMEOUT).unwrap();
+    assert!(
+        server
+            .received()
+            .iter()
+            .any(|frame| frame.opcode == Opcode::Close)
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn ping_keeps_events_for_poll_events() {
+    let server = server("ping_keeps_events");
+    let client = client(&server);
+
+    server.inject_event("ACTIVITY_JOIN", json!({ "secret": "join" }));
+    client.ping(TIMEOUT).unwrap();
+    server.inject_event("ACTIVITY_SPECTATE", json!({ "secret": "spectate" }));
+
+    let messages = poll_until(&client, |messages| {
+        events(messages, "ACTIVITY_SPECTATE").next().is_some()
+    });
+    let order: Vec<&Value> = messages.iter().map(|message| &message["evt"]).collect();
+    assert_eq!(order, ["ACTIVITY_JOIN", "ACTIVITY_SPECTATE"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn poll_events_defers_a_parse_error() {
+    let server = server("defers_a_parse_error");
+    let client = client(&server);
+
+    server.inject_event("A", json!({}));
+    server.inject_event("B", json!({}));
+    server.inject(&Frame::new(Opcode::Frame, b"not json".to_vec()));
+    server.inject_event("C", json!({}));
+    client.ping(TIMEOUT).unwrap();
+
+    let messages = client.poll_events().unwrap();
+    let order: Vec<&Value> = messages.iter().map(|message| &message["evt"]).collect();
+    assert_eq!(order, ["A", "B"]);
+    assert!(matches!(client.poll_events(), Err(Error::Json(_))));
+    let messages = client.poll_events().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["evt"], "C");
+}
+
+#[test]
+#[cfg(unix)]
+fn poll_events_reports_a_close_after_the_events_before_it() {
+    let server = server("reports_a_close");
+    let client = client(&server);
+
+    server.inject_event("A", json!({}));
+    client.ping(TIMEOUT).unwrap();
+    server.close_connections(4000, "Gone");
+
+    let deadline = Instant::now() + TIMEOUT;
+    let mut order = Vec::new();
+    let error = loop {
+        assert!(Instant::now() < deadline, "timed out");
+        match client.poll_events() {
+            Ok(messages) => order.extend(messages.into_iter().map(|m| m["evt"].clone())),
+            Err(error) => break error,
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    assert_eq!(order, ["A"]);
+    assert!(matches!(
+        error,
+        Error::ClosedByServer { code: 4000, message } if message == "Gone"
+    ));
+}
+
+#[test]
+#[cfg(unix)]
+fn reconnect_sends_the_last_activity_again() {
+    let server = server("reconnect");
+    let client = client(&server);
+
+    client
+        .set_activity(Activity::new().set_state("Before".to_string()))
+        .unwrap();
+    activities(&server, 1);
+    server.close_connections(1000, "Restarting");
+
+    client.reconnect().unwrap();
+    let sent = activities(&server, 2);
+    assert_eq!(sent[1]["state"], "Before");
+    assert_eq!(client.ready().user.unwrap().username, "fake-user");
+    client.ping(TIMEOUT).unwrap();
+}
+
+#[test]
+fn build_fails_when_the_handshake_is_rejected() {
+    let server = server("handshake_rejected");
+    server.fail_handshakes(4000, "Invalid Client ID");
+
+    let result = Client::builder(CLIENT_ID)
+        .set_discovery(server.discovery())
+        .build();
+    assert!(matches!(
+        result,
+        Err(Error::ClosedByServer { code: 4000, message }) if message == "Invalid Client ID"
+    ));
+
+    server.accept_handshakes();
+    client(&server);
+}
+
+#[test]
+fn revert_restores_the_previous_activity() {
+    let server = server("revert");
+    let client = client(&server);
+    let activity = |details: &str| Activity::new().set_details(details.to_string());
+
+    client.set_activity(activity("first")).unwrap();
+    client.set_activity(activity("second")).unwrap();
+    assert_eq!(client.revert().unwrap().unwrap().details(), Some("first"));
+    assert_eq!(client.history().len(), 1);
+    assert_eq!(activities(&server, 3)[2]["details"], "first");
+
+    assert!(client.revert().unwrap().is_none());
+    assert!(client.last_activity().is_none());
+}
+
+#[test]
+fn pop_activity_unwinds_nested_pushes() {
+    let server = server("push_and_pop");
+    let client = client(&server);
+    let activity = |details: &str| Activity::new().set_details(details.to_string());
+
+    client.set_activity(activity("game")).unwrap();
+    client.push_activity(activity("match")).unwrap();
+    client.push_activity(activity("replay")).unwrap();
+    assert_eq!(
+        client.pop_activity().unwrap().unwrap().details(),
+        Some("match")
+    );
+    assert_eq!(
+        client.pop_activity().unwrap().unwrap().details(),
+        Some("game")
+    );
+    assert!(client.pop_activity().unwrap().is_none());
+
+    let details: Vec<Value> = activities(&server, 5)
+        .iter()
+        .map(|activity| activity["details"].clone())
+        .collect();
+    assert_eq!(details, ["game", "match", "replay", "match", "game"]);
+}
+
+#[test]
+fn try_set_activity_sends_the_whole_frame() {
+    let server = server("try_set_activity");
+    let client = client(&server);
+
+    let details = "a".repeat(128);
+    client
+        .try_set_activity(Activity::new().set_details(details.clone()))
+        .unwrap();
+    assert_eq!(activities(&server, 1)[0]["details"], details.as_str());
+}